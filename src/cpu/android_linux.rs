@@ -1,34 +1,37 @@
 
 use core::convert::TryInto;
 use core::cell::Cell;
+use core::mem::MaybeUninit;
 use core::time::Duration;
 
-use std::time::Instant;
+use super::{CpuTimes, CpuUsages, ThreadBaseline, ThreadState};
+use crate::utils::process::get_process;
+use crate::Error;
 
 use procfs::process::{Process, Task, Stat};
-use procfs::{CpuInfo, ticks_per_second};
+use procfs::{CpuInfo, ProcError, ticks_per_second};
 
 use once_cell::sync::Lazy;
 
 pub static TICKS_PER_SECOND: Lazy<u64> =
     Lazy::new(ticks_per_second);
 
-pub static CPU_INFO: Lazy<CpuInfo> =
+/// `None` if `/proc/cpuinfo` is unavailable.
+pub static CPU_INFO: Lazy<Option<CpuInfo>> =
     Lazy::new(|| {
         use procfs::Current;
-        CpuInfo::current()
-            .expect("cannot get /proc/cpuinfo")
+        CpuInfo::current().ok()
     });
 
-pub fn current_process() -> anyhow::Result<Process> {
+pub fn current_process() -> crate::Result<Process> {
     Ok(Process::myself()?)
 }
-pub fn current_task() -> anyhow::Result<Task> {
+pub fn current_task() -> crate::Result<Task> {
     let tid: i32 = ThreadId::current().into();
     Ok(current_process()?.task_from_tid(tid)?)
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ThreadId(rustix::thread::Pid);
 
 impl From<ThreadId> for i32 {
@@ -36,29 +39,200 @@ impl From<ThreadId> for i32 {
         val.0.as_raw_nonzero().get()
     }
 }
+impl TryFrom<i32> for ThreadId {
+    type Error = crate::Error;
+
+    fn try_from(tid: i32) -> crate::Result<ThreadId> {
+        match rustix::thread::Pid::from_raw(tid) {
+            Some(pid) => Ok( ThreadId(pid) ),
+            None => Err( Error::InvalidArgument(format!("invalid thread id: {}", tid)) ),
+        }
+    }
+}
 impl ThreadId {
     #[inline]
     pub fn current() -> Self {
         ThreadId( rustix::thread::gettid() )
     }
+
+    /// return the name of this thread, read from `/proc/self/task/{tid}/comm`.
+    ///
+    /// the kernel truncates thread names to 15 bytes.
+    pub fn name(&self) -> crate::Result<String> {
+        let tid: i32 = (*self).into();
+        let comm =
+            std::fs::read_to_string(
+                format!("/proc/self/task/{}/comm", tid)
+            );
+
+        match comm {
+            Ok(comm) => Ok( comm.trim_end_matches('\n').to_string() ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err( Error::ThreadExited { tid: *self } )
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// return all threads in current process whose name is `name`.
+    ///
+    /// `name` is truncated the same way as kernel does before comparing.
+    pub fn find_by_name(name: &str) -> crate::Result<Vec<Self>> {
+        let name = truncate_thread_name(name);
+
+        let mut found = Vec::new();
+        for task in current_process()?.tasks()? {
+            let Ok(task) = task else { continue };
+            let tid: ThreadId = task.tid.try_into()?;
+            match tid.name() {
+                Ok(comm) if comm == name => {
+                    found.push(tid);
+                }
+                _ => {}
+            }
+        }
+        Ok(found)
+    }
+
+    /// look up the OS-level thread id of a `std::thread::Thread`
+    /// that was given a name via `std::thread::Builder`.
+    ///
+    /// fails if the thread is unnamed, or if its name is not unique in current process.
+    pub fn from_std_thread(thread: &std::thread::Thread)
+        -> crate::Result<Self>
+    {
+        let Some(name) = thread.name() else {
+            return Err( Error::InvalidArgument("cannot look up tid of an unnamed thread".to_string()) );
+        };
+
+        let found = Self::find_by_name(name)?;
+        match found.as_slice() {
+            [tid] => Ok(*tid),
+            [] => Err( Error::InvalidArgument(format!("no thread named {:?} in current process", name)) ),
+            _ => Err( Error::InvalidArgument(format!(
+                "{} threads named {:?} in current process",
+                found.len(),
+                name,
+            )) ),
+        }
+    }
+
+    /// return the cpu time this thread consumed since it started.
+    pub fn cpu_time(&self) -> crate::Result<Duration> {
+        get_thread_cputime(*self)
+    }
+
+    /// return the cpu time this thread consumed since it started,
+    /// split into user mode and system mode.
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        get_thread_cputimes(*self)
+    }
+
+    /// return the cpus this thread is allowed to run on, by `sched_getaffinity`.
+    pub fn affinity(&self) -> crate::Result<Vec<usize>> {
+        use rustix::process::{sched_getaffinity, CpuSet};
+
+        let set = sched_getaffinity(Some(self.0))?;
+        let cpus =
+            (0..CpuSet::MAX_CPU)
+            .filter(|&cpu| { set.is_set(cpu) })
+            .collect();
+        Ok(cpus)
+    }
+
+    /// restrict this thread to run on `cpus`, by `sched_setaffinity`.
+    pub fn set_affinity(&self, cpus: &[usize]) -> crate::Result<()> {
+        use rustix::process::{sched_setaffinity, CpuSet};
+
+        if cpus.is_empty() {
+            return Err( Error::InvalidArgument("cannot set an empty cpu affinity".to_string()) );
+        }
+
+        let mut set = CpuSet::new();
+        for &cpu in cpus {
+            if cpu >= CpuSet::MAX_CPU {
+                return Err( Error::InvalidArgument(format!("cpu {} exceeds the max cpu {}", cpu, CpuSet::MAX_CPU - 1)) );
+            }
+            set.set(cpu);
+        }
+        sched_setaffinity(Some(self.0), &set)?;
+        Ok(())
+    }
+}
+
+/// the kernel keeps at most 15 bytes (`TASK_COMM_LEN - 1`) of thread name.
+fn truncate_thread_name(name: &str) -> &str {
+    const MAX_LEN: usize = 15;
+
+    let mut len = name.len().min(MAX_LEN);
+    while ! name.is_char_boundary(len) {
+        len -= 1;
+    }
+    &name[..len]
 }
 
-fn get_thread_stat(tid: ThreadId)
-    -> anyhow::Result<Stat>
+/// get the stat of a thread in current process.
+pub(crate) fn get_thread_stat(tid: ThreadId)
+    -> crate::Result<Stat>
 {
-    let task =
+    let stat =
         current_process()?
-        .task_from_tid( tid.into() )?;
+        .task_from_tid( tid.into() )
+        .and_then(|task| { task.stat() });
 
-    let stat = task.stat()?;
-    Ok(stat)
+    match stat {
+        Ok(stat) => Ok(stat),
+        Err(ProcError::NotFound(_)) => Err( Error::ThreadExited { tid } ),
+        Err(e) => Err(e.into()),
+    }
 }
 
-fn get_thread_cputime(tid: ThreadId)
-    -> anyhow::Result<Duration>
+/// get cpu time of thread split into user mode and system mode.
+///
+/// `getrusage(RUSAGE_THREAD)` is used for current thread, otherwise fallback to procfs.
+fn get_thread_cputimes(tid: ThreadId)
+    -> crate::Result<CpuTimes>
 {
+    if tid == ThreadId::current() {
+        return getrusage_cputimes(libc::RUSAGE_THREAD);
+    }
+
     let stat = get_thread_stat(tid)?;
-    get_stat_cputime(stat)
+    get_stat_cputimes(stat)
+}
+
+/// the cpu-time clock of a thread, see `MAKE_THREAD_CPUCLOCK` in linux `include/linux/posix-timers.h`.
+///
+/// it is the same value as `pthread_getcpuclockid` returns, but works for any tid.
+fn thread_cpuclock_id(tid: ThreadId) -> libc::clockid_t {
+    const CPUCLOCK_SCHED: libc::clockid_t = 2;
+    const CPUCLOCK_PERTHREAD_MASK: libc::clockid_t = 4;
+
+    let tid: i32 = tid.into();
+    ((!tid) << 3) | CPUCLOCK_SCHED | CPUCLOCK_PERTHREAD_MASK
+}
+
+/// get cpu time of thread in nanosecond precision by `clock_gettime`,
+/// fallback to procfs if the clock is not available.
+fn get_thread_cputime(tid: ThreadId)
+    -> crate::Result<Duration>
+{
+    let mut ts = MaybeUninit::<libc::timespec>::uninit();
+    let ret =
+        unsafe {
+            libc::clock_gettime(
+                thread_cpuclock_id(tid),
+                ts.as_mut_ptr()
+            )
+        };
+
+    if ret != 0 {
+        let stat = get_thread_stat(tid)?;
+        return get_stat_cputime(stat);
+    }
+
+    let ts = unsafe { ts.assume_init() };
+    Ok(timespec_to_duration(ts))
 }
 
 enum Ticks {
@@ -88,139 +262,305 @@ impl From<Ticks> for f64 {
 }
 
 fn ticks_to_seconds<T: Into<Ticks>>(ticks: T)
-    -> anyhow::Result<f64>
+    -> crate::Result<f64>
 {
     let ticks: Ticks = ticks.into();
     let ticks: f64 = ticks.into();
 
     let tps: u64 = *TICKS_PER_SECOND;
     if tps == 0 {
-        anyhow::bail!("unexpected zero value of TICKS_PER_SECOND");
+        return Err( Error::Unexpected("zero value of TICKS_PER_SECOND".to_string()) );
     }
 
     Ok(  ticks / (tps as f64)  )
 }
-fn get_stat_cputime(stat: Stat)
-    -> anyhow::Result<Duration>
+fn seconds_to_duration(secs: f64) -> crate::Result<Duration> {
+    if secs < 0.0 {
+        return Err( Error::Unexpected(format!(
+            "cputime({}) should not a negative number!",
+            secs,
+        )) );
+    }
+
+    Ok(Duration::from_secs_f64(secs))
+}
+/// cpu time of the process or thread itself, reaped children is excluded.
+fn get_stat_cputimes(stat: Stat)
+    -> crate::Result<CpuTimes>
 {
     let utime = ticks_to_seconds(stat.utime)?;
     let stime = ticks_to_seconds(stat.stime)?;
+
+    Ok(CpuTimes {
+        user: seconds_to_duration(utime)?,
+        system: seconds_to_duration(stime)?,
+    })
+}
+/// cpu time of the reaped children of process.
+fn get_stat_children_cputimes(stat: Stat)
+    -> crate::Result<CpuTimes>
+{
     let cutime = ticks_to_seconds(stat.cutime)?;
     let cstime = ticks_to_seconds(stat.cstime)?;
 
-    let total_cputime = utime + stime + cutime + cstime;
-    if total_cputime < 0.0 {
-        anyhow::bail!(
-            "cputime({}) should not a negative number!",
-            total_cputime,
-        );
-    }
-
-    Ok(Duration::from_secs_f64( total_cputime.abs() ))
+    Ok(CpuTimes {
+        user: seconds_to_duration(cutime)?,
+        system: seconds_to_duration(cstime)?,
+    })
+}
+fn get_stat_cputime(stat: Stat)
+    -> crate::Result<Duration>
+{
+    Ok( get_stat_cputimes(stat)?.total() )
 }
 
 #[derive(Debug, Clone)]
 pub struct ThreadStat {
     tid: ThreadId,
-    last_stat: Cell<(Duration, Instant)>,
+    baseline: Cell<ThreadBaseline>,
 }
 
 impl TryFrom<ThreadId> for ThreadStat {
-    type Error = anyhow::Error;
+    type Error = crate::Error;
 
     fn try_from(tid: ThreadId)
-        -> anyhow::Result<ThreadStat>
+        -> crate::Result<ThreadStat>
     {
-        let cputime = get_thread_cputime(tid)?;
-        let total_time = Instant::now();
         Ok(ThreadStat {
             tid,
-            last_stat: Cell::new((cputime, total_time)),
+            baseline: Cell::new( ThreadBaseline::new(tid)? ),
         })
     }
 }
 impl ThreadStat {
-    pub fn current() -> anyhow::Result<Self> {
-        ThreadId::current().try_into()
-    }
-
-    #[deprecated]
-    pub fn cur() -> std::io::Result<Self> {
-        match Self::current() {
-            Ok(v) => Ok(v),
-            Err(e) => Err( std::io::Error::other(e) ),
-        }
+    /// return the cpu time and the real time elapsed from last invoke.
+    fn sample(&self) -> crate::Result<(Duration, Duration)> {
+        let mut baseline = self.baseline.get();
+        let sample = baseline.sample(self.tid)?;
+        self.baseline.set(baseline);
+        Ok(sample)
     }
 
-    #[deprecated]
-    pub fn build(tid: ThreadId)-> std::io::Result<Self>{
-        match tid.try_into() {
-            Ok(v) => Ok(v),
-            Err(e) => Err( std::io::Error::other(e) ),
-        }
+    /// return the cpu times and the real time elapsed from last invoke.
+    fn sample_times(&self) -> crate::Result<(CpuTimes, Duration)> {
+        let mut baseline = self.baseline.get();
+        let sample = baseline.sample_times(self.tid)?;
+        self.baseline.set(baseline);
+        Ok(sample)
     }
 
     /// un-normalized
-    pub fn cpu_usage(&self) -> anyhow::Result<f64> {
-        let cputime = get_thread_cputime(self.tid)?;
-        let total_time = Instant::now();
-
-        let (old_cputime, old_total_time) =
-            self.last_stat.replace(
-                (cputime, total_time)
-            );
+    pub fn cpu_usage(&self) -> crate::Result<f64> {
+        let (dt_cputime, dt_total_time) = self.sample()?;
+
+        Ok(
+            dt_cputime.as_secs_f64()
+            /
+            dt_total_time.as_secs_f64()
+        )
+    }
 
+    /// un-normalized, split into user mode and system mode.
+    pub fn cpu_usages(&self) -> crate::Result<CpuUsages> {
+        let (dt_cputimes, dt_total_time) = self.sample_times()?;
+        Ok( dt_cputimes.usages(dt_total_time) )
+    }
 
-        let dt_cputime_f64: f64 =
-            if cputime >= old_cputime {
-                (cputime - old_cputime)
-                .as_secs_f64()
-            } else {
-                let t =
-                    (old_cputime - cputime)
-                    .as_secs_f64();
-                -t
-            };
+    #[deprecated]
+    pub fn cpu(&self) -> std::io::Result<f64> {
+        Ok( self.cpu_usage()? )
+    }
 
-        let dt_total_time_f64: f64 =
-            total_time
-            .saturating_duration_since(old_total_time)
-            .as_secs_f64();
+    pub fn cpu_time(&self) -> crate::Result<Duration> {
+        Ok( self.sample()?.0 )
+    }
 
-        Ok(dt_cputime_f64 / dt_total_time_f64)
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        Ok( self.sample_times()?.0 )
     }
 
-    #[deprecated]
-    pub fn cpu(&self) -> std::io::Result<f64> {
-        match self.cpu_usage() {
-            Ok(v) => Ok(v),
-            Err(e) => Err( std::io::Error::other(e) ),
-        }
+    /// the cpu this thread last ran on, `None` if the kernel does not provide it.
+    pub fn processor(&self) -> crate::Result<Option<usize>> {
+        let stat = get_thread_stat(self.tid)?;
+        Ok( stat.processor.and_then(|cpu| { cpu.try_into().ok() }) )
     }
 
-    pub fn cpu_time(&self) -> anyhow::Result<Duration> {
-        let cputime = get_thread_cputime(self.tid)?;
-        let total_time = Instant::now();
-        let (old_cputime, _old_total_time) =
-            self.last_stat.replace(
-                (cputime, total_time)
-            );
+    pub fn state(&self) -> crate::Result<ThreadState> {
+        let stat = get_thread_stat(self.tid)?;
+        Ok( stat.state.into() )
+    }
+}
 
-        Ok( cputime.saturating_sub(old_cputime) )
+/// get name and cpu time of every thread in current process.
+///
+/// threads that exit while `/proc/self/task` is being walked are skipped.
+pub fn threads_cputime()
+    -> crate::Result<Vec<(ThreadId, String, Duration)>>
+{
+    let mut threads = Vec::new();
+    for task in current_process()?.tasks()? {
+        let Ok(task) = task else { continue };
+        let Ok(stat) = task.stat() else { continue };
+
+        let tid: ThreadId = task.tid.try_into()?;
+        let name = stat.comm.clone();
+        threads.push( (tid, name, get_stat_cputime(stat)?) );
     }
+    Ok(threads)
 }
 
 /// get cpu time of provided PID.
-pub fn process_cputime<T: Into<i32>>(pid: T)
-    -> anyhow::Result<Duration>
+pub fn process_cputime<T: Into<u32>>(pid: T)
+    -> crate::Result<Duration>
+{
+    Ok( process_cputimes(pid)?.total() )
+}
+
+/// get cpu time of provided PID, split into user mode and system mode.
+pub fn process_cputimes<T: Into<u32>>(pid: T)
+    -> crate::Result<CpuTimes>
+{
+    get_stat_cputimes( get_process_stat(pid)? )
+}
+
+/// get cpu time of the reaped children of provided PID.
+pub fn process_children_cputimes<T: Into<u32>>(pid: T)
+    -> crate::Result<CpuTimes>
+{
+    get_stat_children_cputimes( get_process_stat(pid)? )
+}
+
+/// a zombie process is treated as exited.
+pub(crate) fn get_process_stat<T: Into<u32>>(pid: T)
+    -> crate::Result<Stat>
 {
-    let stat = Process::new(pid.into())?.stat()?;
-    get_stat_cputime(stat)
+    let pid: u32 = pid.into();
+
+    let stat =
+        match get_process(pid)?.stat() {
+            Ok(stat) => stat,
+            Err(ProcError::NotFound(_)) => {
+                return Err( Error::ProcessExited { pid } );
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+
+    if matches!(stat.state, 'Z' | 'X' | 'x') {
+        return Err( Error::ProcessExited { pid } );
+    }
+
+    Ok(stat)
 }
 
 /// get cpu time of current process.
-pub fn cpu_time() -> anyhow::Result<Duration> {
+pub fn cpu_time() -> crate::Result<Duration> {
+    Ok( cpu_times()?.total() )
+}
+
+/// get cpu time of current process, split into user mode and system mode.
+pub fn cpu_times() -> crate::Result<CpuTimes> {
     let stat = current_process()?.stat()?;
-    get_stat_cputime(stat)
+    get_stat_cputimes(stat)
+}
+
+
+/// get cpu time of the terminated and waited-for children of current process,
+/// by `getrusage(RUSAGE_CHILDREN)`.
+pub fn children_cpu_times() -> crate::Result<CpuTimes> {
+    getrusage_cputimes(libc::RUSAGE_CHILDREN)
+}
+
+fn getrusage_cputimes(who: libc::c_int) -> crate::Result<CpuTimes> {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    let ret =
+        unsafe {
+            libc::getrusage(
+                who,
+                usage.as_mut_ptr()
+            )
+        };
+
+    if ret != 0 {
+        return Err( std::io::Error::last_os_error().into() );
+    }
+
+    let usage = unsafe { usage.assume_init() };
+
+    Ok(CpuTimes {
+        user: timeval_to_duration(usage.ru_utime),
+        system: timeval_to_duration(usage.ru_stime),
+    })
+}
+
+#[inline]
+fn timeval_to_duration(t: libc::timeval) -> Duration {
+    let secs: Duration =
+        Duration::from_secs(
+            t.tv_sec.try_into().unwrap_or(0)
+        );
+
+    let sub_secs: Duration =
+        Duration::from_micros(
+            t.tv_usec.try_into().unwrap_or(0)
+        );
+
+    secs.saturating_add(sub_secs)
+}
+
+#[inline]
+fn timespec_to_duration(t: libc::timespec) -> Duration {
+    let secs: Duration =
+        Duration::from_secs(
+            t.tv_sec.try_into().unwrap_or(0)
+        );
+
+    let sub_secs: Duration =
+        Duration::from_nanos(
+            t.tv_nsec.try_into().unwrap_or(0)
+        );
+
+    secs.saturating_add(sub_secs)
 }
 
+#[cfg(test)]
+#[allow(clippy::all, clippy::print_stdout)]
+mod tests {
+    use super::*;
+    use test::Bencher;
+
+    #[test]
+    fn test_thread_cpuclock() {
+        let tid = ThreadId::current();
+
+        let mut x = 0u64;
+        for i in 0..100_000_000u64 {
+            x = std::hint::black_box(x.wrapping_add(i));
+        }
+
+        let by_clock = get_thread_cputime(tid).unwrap();
+        let by_procfs = get_stat_cputime(get_thread_stat(tid).unwrap()).unwrap();
+
+        // procfs is in ticks precision.
+        let tick = Duration::from_secs_f64(1.0 / (*TICKS_PER_SECOND as f64));
+        assert!(by_clock + tick * 2 >= by_procfs);
+        assert!(by_procfs + tick * 2 >= by_clock);
+    }
+
+    #[bench]
+    fn bench_thread_cputime_by_clock(b: &mut Bencher) {
+        let tid = ThreadId::current();
+        b.iter(|| {
+            get_thread_cputime(tid).unwrap()
+        });
+    }
+
+    #[bench]
+    fn bench_thread_cputime_by_procfs(b: &mut Bencher) {
+        let tid = ThreadId::current();
+        b.iter(|| {
+            get_stat_cputime(get_thread_stat(tid).unwrap()).unwrap()
+        });
+    }
+}
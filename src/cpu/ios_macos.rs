@@ -3,7 +3,7 @@ use libc::{
     mach_thread_self, thread_info,
 
     // structs, types, and constants
-    rusage, RUSAGE_SELF,
+    rusage, RUSAGE_SELF, RUSAGE_CHILDREN,
 
     time_value_t, timeval,
 
@@ -13,13 +13,16 @@ use libc::{
     THREAD_BASIC_INFO, THREAD_BASIC_INFO_COUNT,
 };
 
+use super::{CpuTimes, CpuUsages};
+
+use core::cell::Cell;
 use core::convert::TryInto;
 use core::mem::MaybeUninit;
 use core::time::Duration;
 
 use std::time::Instant;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ThreadId(u32);
 
 impl From<ThreadId> for u32 {
@@ -32,10 +35,50 @@ impl ThreadId {
     pub fn current() -> Self {
         ThreadId(unsafe { mach_thread_self() })
     }
+
+    /// return the name of this thread, by `pthread_getname_np`.
+    pub fn name(&self) -> crate::Result<String> {
+        let pthread =
+            unsafe {
+                libc::pthread_from_mach_thread_np(self.0)
+            };
+        if pthread == 0 {
+            return Err( crate::Error::ThreadExited { tid: *self } );
+        }
+
+        let mut buf = [0 as libc::c_char; 64];
+        let ret =
+            unsafe {
+                libc::pthread_getname_np(
+                    pthread,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                )
+            };
+        if ret != 0 {
+            return Err( std::io::Error::from_raw_os_error(ret).into() );
+        }
+
+        let name =
+            unsafe { core::ffi::CStr::from_ptr(buf.as_ptr()) };
+        Ok( name.to_string_lossy().into_owned() )
+    }
+
+    /// return the cpu time this thread consumed since it started.
+    pub fn cpu_time(&self) -> crate::Result<Duration> {
+        Ok( self.cpu_times()?.total() )
+    }
+
+    /// return the cpu time this thread consumed since it started,
+    /// split into user mode and system mode.
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        let info = get_thread_basic_info(*self)?;
+        Ok( basic_info_to_cputimes(&info) )
+    }
 }
 
 fn get_thread_basic_info(tid: ThreadId)
-    -> anyhow::Result<thread_basic_info_t>
+    -> crate::Result<thread_basic_info_t>
 {
     let mut basic_info =
         MaybeUninit::<thread_basic_info_t>::uninit();
@@ -53,7 +96,7 @@ fn get_thread_basic_info(tid: ThreadId)
     };
 
     if ret != (KERN_SUCCESS as i32) {
-        return Err( Error::from_raw_os_error(ret) );
+        return Err( std::io::Error::from_raw_os_error(ret).into() );
     }
     Ok(unsafe { basic_info.assume_init() })
 }
@@ -65,9 +108,9 @@ pub struct ThreadStat {
 }
 
 impl TryFrom<ThreadId> for ThreadStat {
-    type Error = anyhow::Error;
+    type Error = crate::Error;
     fn try_from(tid: ThreadId)
-        -> anyhow::Result<Self>
+        -> crate::Result<Self>
     {
         let stat = get_thread_basic_info(tid)?;
         let time = Instant::now();
@@ -79,50 +122,49 @@ impl TryFrom<ThreadId> for ThreadStat {
 }
 
 impl ThreadStat {
-    pub fn current() -> anyhow::Result<Self> {
-        Self::build(ThreadId::current())
+    pub fn current() -> crate::Result<Self> {
+        ThreadId::current().try_into()
     }
 
     #[deprecated]
     pub fn cur() -> std::io::Result<Self> {
-        Self::current()
+        Ok( Self::current()? )
     }
 
     #[deprecated]
     pub fn build(tid: ThreadId)
         -> std::io::Result<Self>
     {
-        tid.try_into()
+        let stat: Self = tid.try_into()?;
+        Ok(stat)
     }
 
-    /// un-normalized
-    pub fn cpu_usage(&self) -> anyhow::Result<f64> {
+    /// return the cpu times and the real time elapsed from last invoke.
+    fn sample(&self) -> crate::Result<(CpuTimes, Duration)> {
         let stat = get_thread_basic_info(self.tid)?;
         let now = Instant::now();
 
         let (old_stat, old_now) =
             self.last_stat.replace( (stat, now) );
 
-        let utime =
-          time_value_to_duration(stat.user_time);
-        let stime =
-          time_value_to_duration(stat.system_time);
+        let cputimes = basic_info_to_cputimes(&stat);
+        let old_cputimes = basic_info_to_cputimes(&old_stat);
 
-        let old_utime =
-          time_value_to_duration(old_stat.user_time);
-        let old_stime =
-          time_value_to_duration(old_stat.system_time);
+        Ok((
+            cputimes.saturating_sub(&old_cputimes),
+            now.saturating_duration_since(old_now),
+        ))
+    }
 
-        let dt_utime = utime.saturating_sub(old_utime);
-        let dt_stime = stime.saturating_sub(old_stime);
+    /// un-normalized
+    pub fn cpu_usage(&self) -> crate::Result<f64> {
+        let (dt_cputimes, dt_duration) = self.sample()?;
 
         let dt_cputime_micros: u128 =
-            dt_utime.saturating_add(dt_stime)
-                    .as_micros();
+            dt_cputimes.total().as_micros();
 
         let mut dt_duration_micros: u128 =
-            now.saturating_duration_since(old_now)
-                .as_micros();
+            dt_duration.as_micros();
         if dt_duration_micros == 0 {
             // this avoids "division by zero"
             dt_duration_micros = 1;
@@ -135,35 +177,30 @@ impl ThreadStat {
         )
     }
 
+    /// un-normalized, split into user mode and system mode.
+    pub fn cpu_usages(&self) -> crate::Result<CpuUsages> {
+        let (dt_cputimes, dt_duration) = self.sample()?;
+        Ok( dt_cputimes.usages(dt_duration) )
+    }
+
     #[deprecated]
     pub fn cpu(&self) -> std::io::Result<f64> {
-        self.cpu_usage()
+        Ok( self.cpu_usage()? )
     }
 
-    pub fn cpu_time(&self) -> anyhow::Result<Duration> {
-        let stat = get_thread_basic_info(self.tid)?;
-        let now = Instant::now();
-
-        let (old_stat, _old_now) =
-            self.last_stat.replace( (stat, now) );
-
-        let utime =
-          time_value_to_duration(stat.user_time);
-        let stime =
-          time_value_to_duration(stat.system_time);
-
-        let old_utime =
-          time_value_to_duration(old_stat.user_time);
-        let old_stime =
-          time_value_to_duration(old_stat.system_time);
-        
-        let dt_utime = utime.saturating_sub(old_utime);
-        let dt_stime = stime.saturating_sub(old_stime);
+    pub fn cpu_time(&self) -> crate::Result<Duration> {
+        Ok( self.sample()?.0.total() )
+    }
 
-        let dt_cputime: Duration =
-            dt_utime.saturating_add(dt_stime);
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        Ok( self.sample()?.0 )
+    }
+}
 
-        Ok(dt_cputime)
+fn basic_info_to_cputimes(info: &thread_basic_info_t) -> CpuTimes {
+    CpuTimes {
+        user: time_value_to_duration(info.user_time),
+        system: time_value_to_duration(info.system_time),
     }
 }
 
@@ -190,8 +227,8 @@ fn timeval_to_duration(t: timeval) -> Duration {
         );
 
     let sub_secs: Duration =
-        Duration::from_nanos(
-            t.tv_nsec.try_into().unwrap_or(0)
+        Duration::from_micros(
+            t.tv_usec.try_into().unwrap_or(0)
         );
 
     secs.saturating_add(sub_secs)
@@ -202,30 +239,39 @@ fn time_value_to_u64(tv: time_value_t) -> u64 {
     time_value_to_duration(tv).as_micros() as u64
 }
 
-pub fn cpu_time() -> anyhow::Result<Duration> {
+pub fn cpu_time() -> crate::Result<Duration> {
+    Ok( cpu_times()?.total() )
+}
+
+pub fn cpu_times() -> crate::Result<CpuTimes> {
+    getrusage_cputimes(RUSAGE_SELF)
+}
+
+/// get cpu time of the terminated and waited-for children of current process.
+pub fn children_cpu_times() -> crate::Result<CpuTimes> {
+    getrusage_cputimes(RUSAGE_CHILDREN)
+}
+
+fn getrusage_cputimes(who: libc::c_int) -> crate::Result<CpuTimes> {
     let mut time = MaybeUninit::<rusage>::uninit();
     let ret =
         unsafe {
             libc::getrusage(
-                RUSAGE_SELF,
+                who,
                 time.as_mut_ptr()
             )
         };
 
     if ret != 0 {
-        return Err(Error::last_os_error());
+        return Err( std::io::Error::last_os_error().into() );
     }
 
     let time = unsafe { time.assume_init() };
 
-    let sec =
-        (time.ru_utime.tv_sec as u64)
-        .saturating_add(time.ru_stime.tv_sec as u64);
-    let nsec =
-        (time.ru_utime.tv_usec as u32)
-        .saturating_add(time.ru_stime.tv_usec as u32)
-        .saturating_mul(1000);
-    Ok(Duration::new(sec, nsec))
+    Ok(CpuTimes {
+        user: timeval_to_duration(time.ru_utime),
+        system: timeval_to_duration(time.ru_stime),
+    })
 }
 
 #[cfg(test)]
@@ -0,0 +1,153 @@
+//! The number of cpus current process is allowed to use.
+//!
+//! Inside a container, the cpu quota and cpuset of cgroup limit the cpu
+//! a process can use, which may be far less than the processors of host.
+//!
+//! | cgroup | quota | cpuset |
+//! | -- | -- | -- |
+//! | v1 | `cpu.cfs_quota_us` / `cpu.cfs_period_us` | `cpuset.effective_cpus` |
+//! | v2 | `cpu.max` | `cpuset.cpus.effective` |
+
+use super::processor_numbers;
+
+/// The cpu limit of current process.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuLimit {
+    /// the cpu bandwidth quota of cgroup, in number of cpus. `None` if unlimited.
+    pub quota: Option<f64>,
+
+    /// the number of cpus in the cpuset of cgroup. `None` if unavailable.
+    pub cpuset: Option<usize>,
+
+    /// see [`processor_numbers`].
+    pub processors: usize,
+}
+
+impl CpuLimit {
+    /// the number of cpus current process is allowed to use at most.
+    pub fn effective(&self) -> f64 {
+        let mut limit = self.processors as f64;
+        if let Some(cpuset) = self.cpuset {
+            limit = limit.min(cpuset as f64);
+        }
+        if let Some(quota) = self.quota {
+            limit = limit.min(quota);
+        }
+        limit
+    }
+}
+
+/// get the cpu limit of current process.
+pub fn cpu_limit() -> crate::Result<CpuLimit> {
+    cpu_limit_of(std::process::id())
+}
+
+/// get the cpu limit of the process specified by `pid`, which is current process on other platforms.
+pub(crate) fn cpu_limit_of(pid: u32) -> crate::Result<CpuLimit> {
+    let processors = processor_numbers()?;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        Ok(CpuLimit {
+            quota: cgroup::cpu_quota(pid)?,
+            cpuset: cgroup::cpuset_count(pid)?,
+            processors,
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let _ = pid;
+        Ok(CpuLimit {
+            quota: None,
+            cpuset: None,
+            processors,
+        })
+    }
+}
+
+/// get the number of cpus current process is allowed to use at most.
+///
+/// it can be a fraction, for example returning 1.5 if the cgroup quota is 150ms per 100ms.
+pub fn effective_cpu_limit() -> crate::Result<f64> {
+    Ok( cpu_limit()?.effective() )
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod cgroup {
+    use crate::utils::cgroup::{Cgroup, CgroupVersion};
+    use crate::utils::sysfs::parse_cpu_list;
+
+    use std::path::Path;
+
+    fn read(dir: &Path, file: &str) -> Option<String> {
+        std::fs::read_to_string(dir.join(file)).ok()
+    }
+
+    /// the quota is hierarchical, the smallest one of ancestors applies.
+    pub fn cpu_quota(pid: u32) -> crate::Result<Option<f64>> {
+        let Some(cgroup) = Cgroup::of_process_controller(pid, "cpu")? else {
+            return Ok(None);
+        };
+
+        let mut quota: Option<f64> = None;
+        for dir in cgroup.ancestors() {
+            let q =
+                match cgroup.version {
+                    CgroupVersion::V1 => {
+                        let q = read(dir, "cpu.cfs_quota_us");
+                        let p = read(dir, "cpu.cfs_period_us");
+                        match (q, p) {
+                            (Some(q), Some(p)) => parse_quota(q.trim(), p.trim()),
+                            _ => None,
+                        }
+                    }
+                    CgroupVersion::V2 => {
+                        read(dir, "cpu.max")
+                        .and_then(|max| {
+                            let (q, p) = max.trim().split_once(' ')?;
+                            parse_quota(q, p)
+                        })
+                    }
+                };
+
+            if let Some(q) = q {
+                quota = Some( quota.map_or(q, |old| { old.min(q) }) );
+            }
+        }
+        Ok(quota)
+    }
+
+    /// `-1` (v1) or `max` (v2) means unlimited.
+    fn parse_quota(quota: &str, period: &str) -> Option<f64> {
+        let quota: f64 = quota.parse().ok()?;
+        let period: f64 = period.parse().ok()?;
+        if quota <= 0.0 || period <= 0.0 {
+            return None;
+        }
+        Some(quota / period)
+    }
+
+    pub fn cpuset_count(pid: u32) -> crate::Result<Option<usize>> {
+        let Some(cgroup) = Cgroup::of_process_controller(pid, "cpuset")? else {
+            return Ok(None);
+        };
+
+        let files: &[&str] =
+            match cgroup.version {
+                CgroupVersion::V1 => &["cpuset.effective_cpus", "cpuset.cpus"],
+                CgroupVersion::V2 => &["cpuset.cpus.effective", "cpuset.cpus"],
+            };
+
+        for file in files {
+            let Ok(list) = cgroup.read(file) else { continue };
+            match parse_cpu_list(&list) {
+                Some(cpus) if ! cpus.is_empty() => {
+                    return Ok(Some(cpus.len()));
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+}
@@ -5,7 +5,22 @@
 //!
 //! The returning value is unnormalized, that is for multi-processor machine,
 //! the cpu usage will beyond 100%, for example returning 2.8 means 280% cpu usage.
-//! If normalized value is what you expected, divide the returning by processor_numbers.
+//! If normalized value is what you expected, divide the returning by processor_numbers,
+//! or use [`ProcessStat::with_normalization`]. Inside a container, [`effective_cpu_limit`]
+//! considers the cpu quota and cpuset of cgroup.
+//!
+//! [`ProcessStat`] and [`ThreadStat`] are `!Sync`, use [`SyncProcessStat`] and [`SyncThreadStat`]
+//! to share a monitor between threads.
+//!
+//! The cpu time of terminated children is not included, use [`children_cpu_time`] to get it.
+//!
+//! On Linux and Android, [`ProcessThreads`] reports the cpu usage of every thread
+//! in current process at once, which helps finding out the threads burning cpu.
+//! [`SystemCpuStat`] reports the system-wide cpu usage, which tells whether the whole machine is saturated.
+//! [`SchedStat`] reports how long a runnable thread waits for a cpu.
+//! [`ThreadStates`] counts the threads blocked on io, which tells a hanging process from an idle one.
+//! [`PerfStat`] counts hardware and software events such as instructions and cycles.
+//! [`CgroupCpuStat`] reports how often the cgroup of current process is throttled by its cpu quota.
 //!
 //! ## Example
 //!
@@ -21,7 +36,7 @@
 //! | platform | thread | process |
 //! | -- | -- | -- |
 //! | windows |[GetThreadTimes] | [GetProcessTimes] |
-//! | linux & android | [/proc/{pid}/task/{tid}/stat][man5] | [clockgettime] |
+//! | linux & android | [clockgettime] | [/proc/{pid}/stat][man5] |
 //! | macos & ios | [thread_info] | [getrusage] |
 //!
 //! [GetThreadTimes]: https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getthreadtimes
@@ -45,7 +60,51 @@ use ios_macos as platform;
 #[cfg(target_os = "windows")]
 use windows as platform;
 
-pub use platform::{cpu_time, ThreadId};
+pub use platform::{cpu_time, cpu_times, children_cpu_times, ThreadId};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use platform::{get_process_stat, get_thread_stat};
+
+mod limit;
+pub use limit::{cpu_limit, effective_cpu_limit, CpuLimit};
+
+mod shared;
+pub use shared::{SyncProcessStat, SyncThreadStat};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod threads;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use threads::{ProcessThreads, ThreadPresence, ThreadUsage};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod system;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use system::{CoreCpuUsage, CpuStateUsage, SystemCpuStat, SystemCpuUsage};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sched;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use sched::{SchedCounters, SchedDelta, SchedStat};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod topology;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use topology::{CpuTopology, LogicalCpu};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod throttle;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use throttle::{CgroupCpuCounters, CgroupCpuDelta, CgroupCpuStat};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod state;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use state::{ThreadState, ThreadStates};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod perf_event;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use perf_event::{PerfCounts, PerfDelta, PerfEvent, PerfStat};
 
 use core::time::Duration;
 use core::cell::Cell;
@@ -53,7 +112,9 @@ use core::cell::Cell;
 use std::time::Instant;
 
 /// logical processor number
-pub fn processor_numbers() -> std::io::Result<usize> {
+///
+/// on Linux and Android, [`CpuTopology`] tells the offline cpus and physical cores as well.
+pub fn processor_numbers() -> crate::Result<usize> {
     Ok( num_cpus::get() )
 
     // (this function only returns "current running CPU cores", not "all of exists CPUs". in most ARM/ARM64 devices, some cores may sleeping/woke for battery saving)
@@ -64,78 +125,324 @@ pub fn processor_numbers() -> std::io::Result<usize> {
     */
 }
 
+/// cpu time split into user mode and system mode.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub user: Duration,
+    pub system: Duration,
+}
+
+impl CpuTimes {
+    /// the cpu time in user mode and system mode.
+    pub fn total(&self) -> Duration {
+        self.user.saturating_add(self.system)
+    }
+
+    pub fn saturating_sub(&self, rhs: &CpuTimes) -> CpuTimes {
+        CpuTimes {
+            user: self.user.saturating_sub(rhs.user),
+            system: self.system.saturating_sub(rhs.system),
+        }
+    }
+
+    pub fn saturating_add(&self, rhs: &CpuTimes) -> CpuTimes {
+        CpuTimes {
+            user: self.user.saturating_add(rhs.user),
+            system: self.system.saturating_add(rhs.system),
+        }
+    }
+
+    /// return the cpu usage if these cpu times are consumed in `real_time`.
+    pub fn usages(&self, real_time: Duration) -> CpuUsages {
+        let real_time = real_time.as_secs_f64();
+        if real_time <= 0.0 {
+            return CpuUsages::default();
+        }
+
+        CpuUsages {
+            user: self.user.as_secs_f64() / real_time,
+            system: self.system.as_secs_f64() / real_time,
+        }
+    }
+}
+
+/// un-normalized cpu usage split into user mode and system mode.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CpuUsages {
+    pub user: f64,
+    pub system: f64,
+}
+
+impl CpuUsages {
+    /// the cpu usage in user mode and system mode.
+    pub fn total(&self) -> f64 {
+        self.user + self.system
+    }
+}
+
+/// The baseline of a thread monitor, shared by the cpu time and its user and system split.
+///
+/// The cpu time is read by [`ThreadId::cpu_time`], the most precise source of every platform.
+/// The split is read by [`ThreadId::cpu_times`] only when it is asked for, and only divides the
+/// cpu time between user mode and system mode, by its ratio since it was read last time.
+#[derive(Debug, Copy, Clone)]
+struct ThreadBaseline {
+    cpu_time: Duration,
+
+    /// the split read last time, `None` if it has never been asked for.
+    split: Option<CpuTimes>,
+
+    at: Instant,
+}
+
+impl ThreadBaseline {
+    fn new(tid: ThreadId) -> crate::Result<Self> {
+        Ok(ThreadBaseline {
+            cpu_time: tid.cpu_time()?,
+            split: None,
+            at: Instant::now(),
+        })
+    }
+
+    /// return the cpu time and the real time elapsed from this baseline, then move it to now.
+    fn sample(&mut self, tid: ThreadId) -> crate::Result<(Duration, Duration)> {
+        let cpu_time = tid.cpu_time()?;
+        let now = Instant::now();
+
+        let cpu_time_delta = cpu_time.saturating_sub(self.cpu_time);
+        let real_time = now.saturating_duration_since(self.at);
+
+        self.cpu_time = cpu_time;
+        self.at = now;
+        Ok( (cpu_time_delta, real_time) )
+    }
+
+    /// same as `sample`, but the cpu time is split into user mode and system mode.
+    fn sample_times(&mut self, tid: ThreadId) -> crate::Result<(CpuTimes, Duration)> {
+        let split = tid.cpu_times()?;
+        let (cpu_time, real_time) = self.sample(tid)?;
+
+        let old_split = self.split.replace(split).unwrap_or_default();
+        let mut ratio = split.saturating_sub(&old_split);
+        if ratio.total().is_zero() {
+            // nothing counted in a short interval, as procfs is in clock ticks precision.
+            ratio = split;
+        }
+
+        let user =
+            match ratio.total().as_secs_f64() {
+                total if total > 0.0 => cpu_time.mul_f64(ratio.user.as_secs_f64() / total),
+                _ => cpu_time,
+            };
+        Ok((
+            CpuTimes {
+                user,
+                system: cpu_time.saturating_sub(user),
+            },
+            real_time,
+        ))
+    }
+}
+
+/// get cpu time of the terminated and waited-for children of current process.
+///
+/// the cpu time of children is not counted in [`cpu_time`] or [`ProcessStat`].
+pub fn children_cpu_time() -> crate::Result<Duration> {
+    Ok( children_cpu_times()?.total() )
+}
+
+/// How [`ProcessStat`] normalizes the cpu usage.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// un-normalized, for example 2.8 means 280% cpu usage.
+    #[default]
+    None,
+
+    /// divided by [`processor_numbers`].
+    Processors,
+
+    /// divided by the effective cpu limit of the monitored process, that is a fraction of
+    /// the cpu allowance of its container, see [`effective_cpu_limit`].
+    CpuLimit,
+}
+
+impl Normalization {
+    /// the divisor for the process specified by `pid`, whose cgroup may differ from current process.
+    fn divisor(&self, pid: u32) -> crate::Result<f64> {
+        match self {
+            Normalization::None => Ok(1.0),
+            Normalization::Processors => Ok( processor_numbers()? as f64 ),
+            Normalization::CpuLimit => Ok( limit::cpu_limit_of(pid)?.effective() ),
+        }
+    }
+}
+
+fn process_cpu_times(pid: u32) -> crate::Result<CpuTimes> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if pid != std::process::id() {
+        return platform::process_cputimes(pid);
+    }
+
+    platform::cpu_times()
+}
+
 /// A struct to monitor process cpu usage
 pub struct ProcessStat {
     pid: u32,
-    last_stat: Cell<(Duration, Instant)>,
+    last_stat: Cell<(CpuTimes, Instant)>,
+    normalization: Normalization,
+
+    /// the divisor of `normalization`, resolved by `with_normalization` and `refresh_limit`.
+    divisor: Cell<f64>,
 }
 
 impl ProcessStat {
     /// return a monitor of current process
-    pub fn current() -> anyhow::Result<Self> {
-        let cpu_time = platform::cpu_time()?;
+    pub fn current() -> crate::Result<Self> {
+        let cpu_times = platform::cpu_times()?;
         let now = Instant::now();
         Ok(ProcessStat {
             pid: std::process::id(),
-            last_stat: Cell::new( (cpu_time, now) ),
+            last_stat: Cell::new( (cpu_times, now) ),
+            normalization: Normalization::None,
+            divisor: Cell::new(1.0),
+        })
+    }
+
+    /// return a monitor of the process specified by `pid`.
+    ///
+    /// monitoring fails with an error once that process exited.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        let cpu_times = platform::process_cputimes(pid)?;
+        let now = Instant::now();
+        Ok(ProcessStat {
+            pid,
+            last_stat: Cell::new( (cpu_times, now) ),
+            normalization: Normalization::None,
+            divisor: Cell::new(1.0),
         })
     }
 
     #[deprecated]
     pub fn cur() -> std::io::Result<Self> {
-        match Self::current() {
-            Ok(v) => Ok(v),
-            Err(e) => Err( std::io::Error::other(e) ),
-        }
+        Ok( Self::current()? )
     }
 
-    /// return the cpu usage from last invoke,
-    /// or when this struct created if it is the first invoke.
-    pub fn cpu(&self) -> anyhow::Result<f64> {
-        let cpu_time = platform::cpu_time()?;
+    /// make the returning of `cpu` and `cpu_usages` normalized.
+    ///
+    /// the cpu limit is resolved once here, call [`ProcessStat::refresh_limit`] if it may change.
+    pub fn with_normalization(mut self, normalization: Normalization) -> crate::Result<Self> {
+        self.divisor = Cell::new( normalization.divisor(self.pid)? );
+        self.normalization = normalization;
+        Ok(self)
+    }
+
+    /// resolve the cpu limit of normalization again, e.g. after the cpu quota of container is updated.
+    pub fn refresh_limit(&self) -> crate::Result<()> {
+        self.divisor.set( self.normalization.divisor(self.pid)? );
+        Ok(())
+    }
+
+    /// return the pid of monitored process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// return the cpu times and the real time elapsed from last invoke.
+    fn sample(&self) -> crate::Result<(CpuTimes, Duration)> {
+        let cpu_times = process_cpu_times(self.pid)?;
         let now = Instant::now();
 
-        let (old_cpu_time, old_now) =
+        let (old_cpu_times, old_now) =
             self.last_stat.replace(
-                (cpu_time, now)
+                (cpu_times, now)
             );
 
-        let real_time: f64 =
-            now.saturating_duration_since(old_now)
-            .as_secs_f64();
+        Ok((
+            cpu_times.saturating_sub(&old_cpu_times),
+            now.saturating_duration_since(old_now),
+        ))
+    }
+
+    /// return the cpu usage from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu(&self) -> crate::Result<f64> {
+        let (cpu_times, real_time) = self.sample()?;
+
+        let real_time: f64 = real_time.as_secs_f64();
+        let cpu_usage: f64 = cpu_times.total().as_secs_f64();
 
-        let cpu_usage: f64 =
-            cpu_time.saturating_sub(old_cpu_time)
-            .as_secs_f64();
+        Ok(cpu_usage / real_time / self.divisor.get())
+    }
 
-        Ok(cpu_usage / real_time)
+    /// return the cpu usage in user mode and system mode respectively from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_usages(&self) -> crate::Result<CpuUsages> {
+        let (cpu_times, real_time) = self.sample()?;
+        let usages = cpu_times.usages(real_time);
+
+        let divisor = self.divisor.get();
+        Ok(CpuUsages {
+            user: usages.user / divisor,
+            system: usages.system / divisor,
+        })
+    }
+
+    /// return the cpu time in user mode and system mode respectively from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        Ok( self.sample()?.0 )
+    }
+
+    /// return the total cpu time of terminated and waited-for children of monitored process.
+    ///
+    /// unlike other methods, this is the cumulative value since the process started.
+    pub fn children_cpu_times(&self) -> crate::Result<CpuTimes> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.pid != std::process::id() {
+            return platform::process_children_cputimes(self.pid);
+        }
+
+        platform::children_cpu_times()
     }
 }
 
 /// A struct to monitor thread cpu usage
-pub struct ThreadStat(platform::ThreadStat);
+///
+/// On Linux & Android, the cpu time is read from the thread cpu-time clock by `clock_gettime`
+/// in nanosecond precision, which does not provide the user and system split. So `cpu_usages`
+/// and `cpu_times` also read the split, by `getrusage(RUSAGE_THREAD)` for current thread or
+/// from `/proc/{pid}/task/{tid}/stat` in clock ticks (usually 10ms) precision for other threads,
+/// and divide the cpu time by its ratio. All methods share one baseline, and their totals agree.
+pub struct ThreadStat {
+    stat: platform::ThreadStat,
+    name: Option<String>,
+}
 
 impl TryFrom<ThreadId> for ThreadStat {
-    type Error = anyhow::Error;
+    type Error = crate::Error;
 
     fn try_from(tid: ThreadId)
-        -> anyhow::Result<ThreadStat>
+        -> crate::Result<ThreadStat>
     {
         let stat: platform::ThreadStat =
             tid.try_into()?;
 
-        Ok( ThreadStat(stat) )
+        Ok(ThreadStat {
+            stat,
+            name: tid.name().ok(),
+        })
     }
 }
 impl ThreadStat {
     /// return a monitor of current thread.
-    pub fn current() -> anyhow::Result<Self> {
-        let stat = platform::ThreadStat::current()?;
-        Ok( Self(stat) )
+    pub fn current() -> crate::Result<Self> {
+        ThreadId::current().try_into()
     }
 
     #[deprecated]
-    pub fn cur() -> anyhow::Result<Self> {
+    pub fn cur() -> crate::Result<Self> {
         Self::current()
     }
 
@@ -144,27 +451,61 @@ impl ThreadStat {
     /// `tid` is **NOT** `std::thread::ThreadId`.
     /// [`ThreadId::current`] can be used to retrieve a valid tid.
     #[deprecated]
-    pub fn build(tid: ThreadId) -> anyhow::Result<Self> {
+    pub fn build(tid: ThreadId) -> crate::Result<Self> {
         tid.try_into()
     }
 
+    /// return the thread name captured when this struct created,
+    /// or `None` if it is not available on this platform.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     /// return the cpu usage from last invoke,
     /// or when this struct created if it is the first invoke.
-    pub fn cpu_usage(&self) -> anyhow::Result<f64> {
-        self.0.cpu_usage()
+    pub fn cpu_usage(&self) -> crate::Result<f64> {
+        self.stat.cpu_usage()
     }
 
     #[deprecated]
     pub fn cpu(&self) -> std::io::Result<f64> {
-        self.0.cpu()
+        self.stat.cpu()
     }
 
     /// return the cpu_time in user mode and system mode from last invoke,
     /// or when this struct created if it is the first invoke.
     pub fn cpu_time(&self)
-        -> anyhow::Result<Duration>
+        -> crate::Result<Duration>
     {
-        self.0.cpu_time()
+        self.stat.cpu_time()
+    }
+
+    /// return the cpu usage in user mode and system mode respectively from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_usages(&self) -> crate::Result<CpuUsages> {
+        self.stat.cpu_usages()
+    }
+
+    /// return the cpu time in user mode and system mode respectively from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_times(&self)
+        -> crate::Result<CpuTimes>
+    {
+        self.stat.cpu_times()
+    }
+
+    /// return the cpu this thread last ran on, read from `/proc/{pid}/task/{tid}/stat`.
+    ///
+    /// compare it with [`ThreadId::affinity`] to find out whether the pinning holds.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn processor(&self) -> crate::Result<Option<usize>> {
+        self.stat.processor()
+    }
+
+    /// return the current scheduling state of this thread.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn state(&self) -> crate::Result<ThreadState> {
+        self.stat.state()
     }
 }
 
@@ -219,4 +560,165 @@ mod test {
         let usage = stat.cpu().unwrap();
         assert!(usage > 0.5)
     }
+
+    #[test]
+    fn test_cpu_limit() {
+        let limit = cpu_limit().unwrap();
+        assert!(limit.processors > 0);
+
+        let effective = effective_cpu_limit().unwrap();
+        assert!(effective > 0.0);
+        assert!(effective <= limit.processors as f64);
+
+        // a child stays in the cgroup of its parent, so it has the same limit.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let mut child = std::process::Command::new("sleep").arg("1").spawn().unwrap();
+            assert_eq!(limit::cpu_limit_of(child.id()).unwrap(), limit);
+
+            let stat =
+                ProcessStat::for_pid(child.id()).unwrap()
+                .with_normalization(Normalization::CpuLimit).unwrap();
+            assert!(stat.cpu().unwrap() <= 1.0);
+            stat.refresh_limit().unwrap();
+            assert!(stat.cpu().unwrap() <= 1.0);
+
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_thread_cpu_times() {
+        let stat = ThreadStat::current().unwrap();
+
+        let mut x = 0u64;
+        for i in 0..300_000_000u64 {
+            x = std::hint::black_box(x.wrapping_add(i));
+        }
+        let times = stat.cpu_times().unwrap();
+        assert!(times.user > times.system);
+
+        for _ in 0..100_000 {
+            let _ = std::hint::black_box(std::fs::metadata("/"));
+        }
+        let usages = stat.cpu_usages().unwrap();
+        assert!(usages.system > 0.0);
+        assert!((usages.total() - usages.user - usages.system).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_thread_shared_baseline() {
+        // whichever method moved the baseline, the other one counts from there.
+        let stat = ThreadStat::current().unwrap();
+        let mut x = 0u64;
+        for i in 0..100_000_000u64 {
+            x = std::hint::black_box(x.wrapping_add(i));
+        }
+        let busy = stat.cpu_time().unwrap();
+        let idle = stat.cpu_times().unwrap();
+        assert!(idle.total() < busy);
+
+        for i in 0..100_000_000u64 {
+            x = std::hint::black_box(x.wrapping_add(i));
+        }
+        let busy = stat.cpu_times().unwrap();
+        let idle = stat.cpu_time().unwrap();
+        assert!(idle < busy.total());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_children_cpu_time() {
+        let before = children_cpu_time().unwrap();
+
+        let status =
+            std::process::Command::new("sh")
+            .arg("-c")
+            .arg("i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let after = children_cpu_time().unwrap();
+        assert!(after > before);
+
+        let stat = ProcessStat::current().unwrap();
+        let children = stat.children_cpu_times().unwrap();
+        assert!(children.total() >= after - Duration::from_millis(10));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_process_usage_for_pid() {
+        let mut child =
+            std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+
+        let stat = ProcessStat::for_pid(child.id()).unwrap();
+        assert_eq!(stat.pid(), child.id());
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let usage = stat.cpu().unwrap();
+        assert!(usage < 0.5);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let err = stat.cpu().unwrap_err();
+        assert!(err.to_string().contains("exited"));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_thread_name() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle =
+            std::thread::Builder::new()
+            .name("perfmon-test-name".to_string())
+            .spawn(move || {
+                let stat = ThreadStat::current().unwrap();
+                tx.send(stat.name().map(|x| x.to_string())).unwrap();
+                std::thread::park();
+            })
+            .unwrap();
+
+        // truncated to 15 bytes by kernel.
+        let name = rx.recv().unwrap();
+        assert_eq!(name.as_deref(), Some("perfmon-test-na"));
+
+        let tid = ThreadId::from_std_thread(handle.thread()).unwrap();
+        assert_eq!(tid.name().unwrap(), "perfmon-test-na");
+
+        handle.thread().unpark();
+        handle.join().unwrap();
+        assert!(matches!(tid.name(), Err(crate::Error::ThreadExited { .. })));
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_thread_affinity() {
+        std::thread::spawn(|| {
+            let tid = ThreadId::current();
+            let allowed = tid.affinity().unwrap();
+            assert!(! allowed.is_empty());
+
+            let cpu = *allowed.last().unwrap();
+            tid.set_affinity(&[cpu]).unwrap();
+            assert_eq!(tid.affinity().unwrap(), vec![cpu]);
+
+            // let the scheduler migrate this thread.
+            std::thread::yield_now();
+            let _ = (0..1_000_000).into_iter().sum::<u64>();
+
+            let stat = ThreadStat::current().unwrap();
+            assert_eq!(stat.processor().unwrap(), Some(cpu));
+
+            assert!(tid.set_affinity(&[]).is_err());
+        })
+        .join()
+        .unwrap();
+    }
 }
@@ -0,0 +1,407 @@
+//! Hardware and software counters of a thread or process, by
+//! [perf_event_open](https://man7.org/linux/man-pages/man2/perf_event_open.2.html).
+//!
+//! Hardware events (instructions, cycles, cache misses, branch misses) require
+//! a PMU, which is often missing in virtual machines. They are reported as `None`
+//! in that case, while software events (task-clock, page faults, context switches)
+//! work everywhere.
+//!
+//! The access is controlled by `/proc/sys/kernel/perf_event_paranoid`, kernel mode
+//! is excluded from counting if it is not allowed.
+//!
+//! ```
+//! # use perfmon::cpu::PerfStat;
+//! if let Ok(stat) = PerfStat::current_thread() {
+//!     let _ = (0..1_000_000).into_iter().sum::<u64>();
+//!     let delta = stat.sample().unwrap();
+//!     println!("cpu usage: {:?}, ipc: {:?}", delta.cpu_usage(), delta.ipc());
+//! }
+//! ```
+
+use super::ThreadId;
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Instant;
+
+use crate::utils::process::get_process;
+use crate::Error;
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_SOFTWARE: u32 = 1;
+
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+const PERF_COUNT_SW_TASK_CLOCK: u64 = 1;
+const PERF_COUNT_SW_PAGE_FAULTS: u64 = 2;
+const PERF_COUNT_SW_CONTEXT_SWITCHES: u64 = 3;
+
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+const ATTR_FLAG_INHERIT: u64 = 1 << 1;
+const ATTR_FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+/// `struct perf_event_attr` of `PERF_ATTR_SIZE_VER0`, newer fields are not needed.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+/// An event that can be counted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PerfEvent {
+    Instructions,
+    Cycles,
+    CacheMisses,
+    BranchMisses,
+    TaskClock,
+    PageFaults,
+    ContextSwitches,
+}
+
+impl PerfEvent {
+    pub const ALL: [PerfEvent; 7] = [
+        PerfEvent::Instructions,
+        PerfEvent::Cycles,
+        PerfEvent::CacheMisses,
+        PerfEvent::BranchMisses,
+        PerfEvent::TaskClock,
+        PerfEvent::PageFaults,
+        PerfEvent::ContextSwitches,
+    ];
+
+    /// whether this event requires a hardware PMU.
+    pub fn is_hardware(&self) -> bool {
+        self.type_config().0 == PERF_TYPE_HARDWARE
+    }
+
+    fn type_config(&self) -> (u32, u64) {
+        match self {
+            PerfEvent::Instructions => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS),
+            PerfEvent::Cycles => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES),
+            PerfEvent::CacheMisses => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CACHE_MISSES),
+            PerfEvent::BranchMisses => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_MISSES),
+            PerfEvent::TaskClock => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_TASK_CLOCK),
+            PerfEvent::PageFaults => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_PAGE_FAULTS),
+            PerfEvent::ContextSwitches => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_CONTEXT_SWITCHES),
+        }
+    }
+}
+
+/// A counter of an event attached to a thread.
+#[derive(Debug)]
+struct Counter {
+    fd: OwnedFd,
+}
+
+impl Counter {
+    fn open(event: PerfEvent, tid: i32, inherit: bool) -> std::io::Result<Self> {
+        let (type_, config) = event.type_config();
+        let mut flags = ATTR_FLAG_EXCLUDE_HV;
+        if inherit {
+            flags |= ATTR_FLAG_INHERIT;
+        }
+
+        match Self::open_with(type_, config, flags, tid) {
+            // kernel mode is not allowed to count by `perf_event_paranoid`.
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EACCES) | Some(libc::EPERM)) => {
+                Self::open_with(type_, config, flags | ATTR_FLAG_EXCLUDE_KERNEL, tid)
+            }
+            ret => ret,
+        }
+    }
+
+    fn open_with(type_: u32, config: u64, flags: u64, tid: i32) -> std::io::Result<Self> {
+        let attr = PerfEventAttr {
+            type_,
+            size: core::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+            flags,
+            ..PerfEventAttr::default()
+        };
+
+        let fd =
+            unsafe {
+                libc::syscall(
+                    libc::SYS_perf_event_open,
+                    &attr as *const PerfEventAttr,
+                    tid,
+                    -1 as libc::c_int,
+                    -1 as libc::c_int,
+                    PERF_FLAG_FD_CLOEXEC,
+                )
+            };
+        if fd < 0 {
+            return Err( std::io::Error::last_os_error() );
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+        Ok(Counter { fd })
+    }
+
+    /// read the value, scaled if the counter is multiplexed with others.
+    fn read(&self) -> std::io::Result<u64> {
+        let mut buf = [0u64; 3];
+        let len = core::mem::size_of_val(&buf);
+        let ret =
+            unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    len,
+                )
+            };
+        if ret < 0 {
+            return Err( std::io::Error::last_os_error() );
+        }
+        if ret as usize != len {
+            return Err( std::io::Error::other("short read of perf event counter") );
+        }
+
+        let [value, enabled, running] = buf;
+        if running == 0 || running >= enabled {
+            return Ok(value);
+        }
+        Ok( (value as f64 * enabled as f64 / running as f64) as u64 )
+    }
+}
+
+/// cumulative counts of events, `None` if the event is unavailable.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PerfCounts {
+    pub instructions: Option<u64>,
+    pub cycles: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub branch_misses: Option<u64>,
+
+    /// the time spent on cpu.
+    pub task_clock: Option<Duration>,
+
+    pub page_faults: Option<u64>,
+    pub context_switches: Option<u64>,
+}
+
+impl PerfCounts {
+    fn set(&mut self, event: PerfEvent, value: u64) {
+        match event {
+            PerfEvent::Instructions => self.instructions = Some(value),
+            PerfEvent::Cycles => self.cycles = Some(value),
+            PerfEvent::CacheMisses => self.cache_misses = Some(value),
+            PerfEvent::BranchMisses => self.branch_misses = Some(value),
+            PerfEvent::TaskClock => self.task_clock = Some(Duration::from_nanos(value)),
+            PerfEvent::PageFaults => self.page_faults = Some(value),
+            PerfEvent::ContextSwitches => self.context_switches = Some(value),
+        }
+    }
+
+    pub fn saturating_sub(&self, rhs: &PerfCounts) -> PerfCounts {
+        fn sub(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            a.zip(b).map(|(a, b)| { a.saturating_sub(b) })
+        }
+
+        PerfCounts {
+            instructions: sub(self.instructions, rhs.instructions),
+            cycles: sub(self.cycles, rhs.cycles),
+            cache_misses: sub(self.cache_misses, rhs.cache_misses),
+            branch_misses: sub(self.branch_misses, rhs.branch_misses),
+            task_clock:
+                self.task_clock.zip(rhs.task_clock)
+                .map(|(a, b)| { a.saturating_sub(b) }),
+            page_faults: sub(self.page_faults, rhs.page_faults),
+            context_switches: sub(self.context_switches, rhs.context_switches),
+        }
+    }
+
+    /// instructions per cycle, `None` if hardware events are unavailable.
+    pub fn ipc(&self) -> Option<f64> {
+        match (self.instructions, self.cycles) {
+            (Some(_), Some(0)) => Some(0.0),
+            (Some(instructions), Some(cycles)) => Some(instructions as f64 / cycles as f64),
+            _ => None,
+        }
+    }
+}
+
+/// counts of events between two samples.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PerfDelta {
+    pub counts: PerfCounts,
+
+    /// the real time elapsed between two samples.
+    pub elapsed: Duration,
+}
+
+impl PerfDelta {
+    /// instructions per cycle, `None` if hardware events are unavailable.
+    pub fn ipc(&self) -> Option<f64> {
+        self.counts.ipc()
+    }
+
+    /// the task-clock divided by real time, un-normalized.
+    pub fn cpu_usage(&self) -> Option<f64> {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return Some(0.0);
+        }
+        self.counts.task_clock.map(|t| { t.as_secs_f64() / elapsed })
+    }
+}
+
+/// A struct to monitor perf events of a thread or process.
+pub struct PerfStat {
+    /// a process has a counter per thread for each event.
+    counters: Vec<(PerfEvent, Vec<Counter>)>,
+    last_stat: Cell<(PerfCounts, Instant)>,
+}
+
+impl PerfStat {
+    /// open counters of `events` for `tids`, events failed to open are skipped.
+    fn open(events: &[PerfEvent], tids: &[i32], inherit: bool) -> crate::Result<Self> {
+        let mut counters = Vec::new();
+        let mut last_error = None;
+
+        for &event in events {
+            let mut event_counters = Vec::new();
+            for &tid in tids {
+                match Counter::open(event, tid, inherit) {
+                    Ok(counter) => event_counters.push(counter),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            if ! event_counters.is_empty() {
+                counters.push( (event, event_counters) );
+            }
+        }
+
+        if counters.is_empty() {
+            let Some(e) = last_error else {
+                return Err( Error::InvalidArgument("no perf event is requested".to_string()) );
+            };
+            return Err( e.into() );
+        }
+
+        let stat = PerfStat {
+            counters,
+            last_stat: Cell::new( (PerfCounts::default(), Instant::now()) ),
+        };
+        stat.last_stat.set( (stat.counts()?, Instant::now()) );
+        Ok(stat)
+    }
+
+    /// return a monitor of current thread, with all events.
+    pub fn current_thread() -> crate::Result<Self> {
+        Self::for_thread(ThreadId::current())
+    }
+
+    /// return a monitor of specified thread, with all events.
+    pub fn for_thread(tid: ThreadId) -> crate::Result<Self> {
+        Self::with_events(tid, &PerfEvent::ALL)
+    }
+
+    /// return a monitor of specified thread, with specified events.
+    pub fn with_events(tid: ThreadId, events: &[PerfEvent]) -> crate::Result<Self> {
+        Self::open(events, &[tid.into()], false)
+    }
+
+    /// return a monitor of current process, with all events.
+    pub fn current_process() -> crate::Result<Self> {
+        Self::for_pid(std::process::id())
+    }
+
+    /// return a monitor of the process specified by `pid`, with all events.
+    ///
+    /// threads alive now and threads they spawn later are counted,
+    /// the counts of exited threads are kept.
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        let mut tids = Vec::new();
+        for task in get_process(pid)?.tasks()? {
+            let Ok(task) = task else { continue };
+            tids.push(task.tid);
+        }
+        Self::open(&PerfEvent::ALL, &tids, true)
+    }
+
+    /// return the events counted, hardware events are missing without a PMU.
+    pub fn events(&self) -> Vec<PerfEvent> {
+        self.counters.iter().map(|(event, _)| { *event }).collect()
+    }
+
+    /// return the cumulative counts, it does not affect the baseline of `sample`.
+    pub fn counts(&self) -> crate::Result<PerfCounts> {
+        let mut counts = PerfCounts::default();
+        for (event, counters) in self.counters.iter() {
+            let mut total = 0u64;
+            for counter in counters {
+                total = total.saturating_add( counter.read()? );
+            }
+            counts.set(*event, total);
+        }
+        Ok(counts)
+    }
+
+    /// return the counts from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn sample(&self) -> crate::Result<PerfDelta> {
+        let counts = self.counts()?;
+        let now = Instant::now();
+
+        let (old_counts, old_now) =
+            self.last_stat.replace(
+                (counts, now)
+            );
+
+        Ok(PerfDelta {
+            counts: counts.saturating_sub(&old_counts),
+            elapsed: now.saturating_duration_since(old_now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_perf_stat() {
+        // perf events may be disabled by `perf_event_paranoid` or seccomp.
+        let Ok(stat) = PerfStat::current_thread() else { return };
+        assert!(! stat.events().is_empty());
+
+        let _ = (0..10_000_000u64).map(std::hint::black_box).sum::<u64>();
+        std::thread::sleep(Duration::from_millis(1));
+        let delta = stat.sample().unwrap();
+
+        if let Some(task_clock) = delta.counts.task_clock {
+            assert!(task_clock > Duration::ZERO);
+            assert!(delta.cpu_usage().unwrap() <= 1.5);
+        }
+        if let Some(context_switches) = delta.counts.context_switches {
+            assert!(context_switches >= 1);
+        }
+        assert_eq!(delta.ipc().is_some(), stat.events().contains(&PerfEvent::Instructions)
+            && stat.events().contains(&PerfEvent::Cycles));
+
+        let process = PerfStat::current_process().unwrap();
+        assert!(process.counts().unwrap().task_clock.is_some());
+    }
+}
@@ -0,0 +1,391 @@
+//! Scheduler statistics of a thread or process, which tells whether a runnable
+//! thread is starved of cpu.
+//!
+//! | field | source |
+//! | -- | -- |
+//! | run time, run-queue wait time, timeslices | `/proc/{pid}/task/{tid}/schedstat` |
+//! | voluntary & involuntary context switches | `/proc/{pid}/task/{tid}/status` |
+//! | context switches of current process, including exited threads | `getrusage(RUSAGE_SELF)` |
+//! | migrations | `/proc/{pid}/task/{tid}/sched` (requires `CONFIG_SCHED_DEBUG`) |
+//!
+//! See [sched-stats](https://docs.kernel.org/scheduler/sched-stats.html) for details.
+//!
+//! ```
+//! # use perfmon::cpu::SchedStat;
+//! let stat = SchedStat::current_thread().unwrap();
+//! std::thread::sleep(std::time::Duration::from_millis(10));
+//! let delta = stat.sample().unwrap();
+//! println!("run-queue wait: {:.2}%", delta.wait_usage() * 100f64);
+//! ```
+
+use super::ThreadId;
+use crate::utils::process::get_process;
+use crate::Error;
+
+use core::cell::RefCell;
+use core::mem::MaybeUninit;
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use procfs::process::Task;
+use procfs::ProcError;
+
+/// cumulative scheduler counters of a thread, or the sum of all threads of a process.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SchedCounters {
+    /// time spent on the cpu.
+    pub run_time: Duration,
+
+    /// time spent waiting on a run-queue, that is runnable but not running.
+    pub wait_time: Duration,
+
+    /// the number of timeslices run on a cpu.
+    pub timeslices: u64,
+
+    /// the number of context switches because of waiting for a resource (e.g. io, lock).
+    pub voluntary_ctxt_switches: u64,
+
+    /// the number of context switches because of preempted by scheduler.
+    pub involuntary_ctxt_switches: u64,
+
+    /// the number of migrations between cpus, `None` if the kernel does not provide it.
+    pub migrations: Option<u64>,
+}
+
+impl SchedCounters {
+    pub fn saturating_sub(&self, rhs: &SchedCounters) -> SchedCounters {
+        SchedCounters {
+            run_time: self.run_time.saturating_sub(rhs.run_time),
+            wait_time: self.wait_time.saturating_sub(rhs.wait_time),
+            timeslices: self.timeslices.saturating_sub(rhs.timeslices),
+            voluntary_ctxt_switches:
+                self.voluntary_ctxt_switches.saturating_sub(rhs.voluntary_ctxt_switches),
+            involuntary_ctxt_switches:
+                self.involuntary_ctxt_switches.saturating_sub(rhs.involuntary_ctxt_switches),
+            migrations:
+                self.migrations.zip(rhs.migrations)
+                .map(|(a, b)| { a.saturating_sub(b) }),
+        }
+    }
+
+    fn saturating_add(&self, rhs: &SchedCounters) -> SchedCounters {
+        SchedCounters {
+            run_time: self.run_time.saturating_add(rhs.run_time),
+            wait_time: self.wait_time.saturating_add(rhs.wait_time),
+            timeslices: self.timeslices.saturating_add(rhs.timeslices),
+            voluntary_ctxt_switches:
+                self.voluntary_ctxt_switches.saturating_add(rhs.voluntary_ctxt_switches),
+            involuntary_ctxt_switches:
+                self.involuntary_ctxt_switches.saturating_add(rhs.involuntary_ctxt_switches),
+            migrations:
+                self.migrations.zip(rhs.migrations)
+                .map(|(a, b)| { a.saturating_add(b) }),
+        }
+    }
+}
+
+/// scheduler counters between two samples.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SchedDelta {
+    pub counters: SchedCounters,
+
+    /// the real time elapsed between two samples.
+    pub elapsed: Duration,
+}
+
+impl SchedDelta {
+    fn per_sec(&self, count: u64) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        count as f64 / elapsed
+    }
+
+    /// the time spent on cpu divided by real time, un-normalized.
+    pub fn run_usage(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        self.counters.run_time.as_secs_f64() / elapsed
+    }
+
+    /// the time spent waiting on run-queue divided by real time, un-normalized.
+    pub fn wait_usage(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        self.counters.wait_time.as_secs_f64() / elapsed
+    }
+
+    /// the average time waiting on run-queue before each timeslice.
+    pub fn avg_wait_per_timeslice(&self) -> Duration {
+        match u32::try_from(self.counters.timeslices) {
+            Ok(0) => Duration::ZERO,
+            Ok(n) => self.counters.wait_time / n,
+            Err(_) => Duration::from_secs_f64(
+                self.counters.wait_time.as_secs_f64()
+                / (self.counters.timeslices as f64)
+            ),
+        }
+    }
+
+    pub fn voluntary_ctxt_switches_per_sec(&self) -> f64 {
+        self.per_sec(self.counters.voluntary_ctxt_switches)
+    }
+
+    pub fn involuntary_ctxt_switches_per_sec(&self) -> f64 {
+        self.per_sec(self.counters.involuntary_ctxt_switches)
+    }
+
+    pub fn migrations_per_sec(&self) -> Option<f64> {
+        self.counters.migrations.map(|m| { self.per_sec(m) })
+    }
+}
+
+/// parse `se.nr_migrations` from `/proc/{pid}/task/{tid}/sched`.
+fn read_migrations(task: &Task) -> Option<u64> {
+    let path = format!("/proc/{}/task/{}/sched", task.pid, task.tid);
+    let content = std::fs::read_to_string(path).ok()?;
+
+    content.lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if key.trim() != "se.nr_migrations" {
+                return None;
+            }
+            value.trim().parse().ok()
+        })
+}
+
+fn read_task_counters(task: &Task) -> crate::Result<SchedCounters> {
+    let schedstat = task.schedstat()?;
+    let status = task.status()?;
+
+    Ok(SchedCounters {
+        run_time: Duration::from_nanos(schedstat.sum_exec_runtime),
+        wait_time: Duration::from_nanos(schedstat.run_delay),
+        timeslices: schedstat.pcount,
+        voluntary_ctxt_switches: status.voluntary_ctxt_switches.unwrap_or(0),
+        involuntary_ctxt_switches: status.nonvoluntary_ctxt_switches.unwrap_or(0),
+        migrations: read_migrations(task),
+    })
+}
+
+/// the context switches of current process, including the threads that have exited.
+fn group_ctxt_switches() -> crate::Result<(u64, u64)> {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    let ret =
+        unsafe {
+            libc::getrusage(
+                libc::RUSAGE_SELF,
+                usage.as_mut_ptr()
+            )
+        };
+
+    if ret != 0 {
+        return Err( std::io::Error::last_os_error().into() );
+    }
+
+    let usage = unsafe { usage.assume_init() };
+    Ok(( usage.ru_nvcsw as u64, usage.ru_nivcsw as u64 ))
+}
+
+fn sum(counters: impl Iterator<Item = SchedCounters>) -> SchedCounters {
+    counters
+        .reduce(|total, c| { total.saturating_add(&c) })
+        .unwrap_or_default()
+}
+
+/// the counters of each thread at a moment.
+#[derive(Debug, Clone, Default)]
+struct Snapshot {
+    tasks: HashMap<i32, SchedCounters>,
+
+    /// the context switches of the whole process, only available for current process.
+    group_ctxt_switches: Option<(u64, u64)>,
+}
+
+impl Snapshot {
+    fn with_group_ctxt_switches(&self, mut counters: SchedCounters, old: (u64, u64)) -> SchedCounters {
+        if let Some((voluntary, involuntary)) = self.group_ctxt_switches {
+            counters.voluntary_ctxt_switches = voluntary.saturating_sub(old.0);
+            counters.involuntary_ctxt_switches = involuntary.saturating_sub(old.1);
+        }
+        counters
+    }
+
+    fn counters(&self) -> SchedCounters {
+        let total = sum( self.tasks.values().copied() );
+        self.with_group_ctxt_switches(total, (0, 0))
+    }
+
+    /// the counters since `old`, summed per thread so that an exited thread does not
+    /// cancel out the counters of others, and a new thread counts from its start.
+    fn saturating_sub(&self, old: &Snapshot) -> SchedCounters {
+        let delta =
+            sum(
+                self.tasks.iter()
+                .map(|(tid, counters)| {
+                    match old.tasks.get(tid) {
+                        Some(old) => counters.saturating_sub(old),
+                        None => *counters,
+                    }
+                })
+            );
+        self.with_group_ctxt_switches(delta, old.group_ctxt_switches.unwrap_or((0, 0)))
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    Thread { pid: u32, tid: ThreadId },
+    Process(u32),
+}
+
+impl Target {
+    fn read_snapshot(&self) -> crate::Result<Snapshot> {
+        match *self {
+            Target::Thread { pid, tid } => {
+                let counters =
+                    match get_process(pid)?.task_from_tid(tid.into()) {
+                        Ok(task) => read_task_counters(&task)?,
+                        Err(ProcError::NotFound(_)) => {
+                            return Err( Error::ThreadExited { tid } );
+                        }
+                        Err(e) => {
+                            return Err(e.into());
+                        }
+                    };
+
+                Ok(Snapshot {
+                    tasks: HashMap::from([ (tid.into(), counters) ]),
+                    group_ctxt_switches: None,
+                })
+            }
+            Target::Process(pid) => {
+                let mut tasks = HashMap::new();
+                for task in get_process(pid)?.tasks()? {
+                    // the thread exited while walking tasks.
+                    let Ok(task) = task else { continue };
+                    let Ok(counters) = read_task_counters(&task) else { continue };
+                    tasks.insert(task.tid, counters);
+                }
+
+                let group_ctxt_switches =
+                    if pid == std::process::id() {
+                        Some( group_ctxt_switches()? )
+                    } else {
+                        None
+                    };
+
+                Ok(Snapshot { tasks, group_ctxt_switches })
+            }
+        }
+    }
+}
+
+/// A struct to monitor scheduler statistics of a thread or process.
+pub struct SchedStat {
+    target: Target,
+    last_stat: RefCell<(Snapshot, Instant)>,
+}
+
+impl SchedStat {
+    fn new(target: Target) -> crate::Result<Self> {
+        let snapshot = target.read_snapshot()?;
+        let now = Instant::now();
+        Ok(SchedStat {
+            target,
+            last_stat: RefCell::new( (snapshot, now) ),
+        })
+    }
+
+    /// return a monitor of current thread.
+    pub fn current_thread() -> crate::Result<Self> {
+        Self::for_thread(ThreadId::current())
+    }
+
+    /// return a monitor of specified thread in current process.
+    pub fn for_thread(tid: ThreadId) -> crate::Result<Self> {
+        Self::new(Target::Thread {
+            pid: std::process::id(),
+            tid,
+        })
+    }
+
+    /// return a monitor of current process.
+    ///
+    /// the context switches of current process include the threads that have exited.
+    pub fn current_process() -> crate::Result<Self> {
+        Self::for_pid(std::process::id())
+    }
+
+    /// return a monitor of the process specified by `pid`.
+    ///
+    /// the counters of a process are summed over its alive threads, so the cumulative
+    /// [`counters`](Self::counters) may decrease when a thread exits. `sample` takes
+    /// the difference thread by thread, so it only misses the counters of a thread
+    /// between the last sample and its exit.
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        Self::new( Target::Process(pid) )
+    }
+
+    /// return the cumulative counters, it does not affect the baseline of `sample`.
+    pub fn counters(&self) -> crate::Result<SchedCounters> {
+        Ok( self.target.read_snapshot()?.counters() )
+    }
+
+    /// return the counters from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn sample(&self) -> crate::Result<SchedDelta> {
+        let snapshot = self.target.read_snapshot()?;
+        let now = Instant::now();
+
+        let (old_snapshot, old_now) =
+            self.last_stat.replace(
+                (snapshot.clone(), now)
+            );
+
+        Ok(SchedDelta {
+            counters: snapshot.saturating_sub(&old_snapshot),
+            elapsed: now.saturating_duration_since(old_now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sched_stat() {
+        let stat = SchedStat::current_thread().unwrap();
+
+        for _ in 0..10 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let delta = stat.sample().unwrap();
+        assert!(delta.counters.voluntary_ctxt_switches >= 10);
+        assert!(delta.voluntary_ctxt_switches_per_sec() > 0.0);
+        assert!(delta.elapsed >= Duration::from_millis(10));
+
+        let process = SchedStat::current_process().unwrap();
+        let me = stat.counters().unwrap();
+        let total = process.counters().unwrap();
+        assert!(total.voluntary_ctxt_switches >= me.voluntary_ctxt_switches);
+
+        // the switches of an exited thread are still counted.
+        std::thread::spawn(|| {
+            for _ in 0..10 {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }).join().unwrap();
+        let delta = process.sample().unwrap();
+        assert!(delta.counters.voluntary_ctxt_switches >= 10);
+    }
+}
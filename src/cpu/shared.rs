@@ -0,0 +1,334 @@
+//! Thread-safe variants of [`ProcessStat`](super::ProcessStat) and [`ThreadStat`](super::ThreadStat).
+//!
+//! The baseline is guarded by a mutex, so a monitor can be put in an `Arc` and
+//! sampled from any thread. Every consumer sampling the same monitor moves the same
+//! baseline, so each of them should take its own [`reader`](SyncProcessStat::reader)
+//! to avoid stealing deltas from others.
+//!
+//! ```
+//! # use perfmon::cpu::SyncProcessStat;
+//! # use std::sync::Arc;
+//! let stat = Arc::new(SyncProcessStat::current().unwrap());
+//!
+//! let reader = stat.reader().unwrap();
+//! let handle = std::thread::spawn(move || { reader.cpu().unwrap() });
+//!
+//! println!("cpu usage: {:.2}%", stat.cpu().unwrap() * 100f64);
+//! println!("cpu usage seen by another thread: {:.2}%", handle.join().unwrap() * 100f64);
+//! ```
+
+use super::{process_cpu_times, CpuTimes, CpuUsages, Normalization, ThreadBaseline, ThreadId};
+
+use core::time::Duration;
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A baseline that can be replaced from any thread.
+#[derive(Debug)]
+struct Baseline {
+    last_stat: Mutex<(CpuTimes, Instant)>,
+}
+
+impl Baseline {
+    fn new(cpu_times: CpuTimes) -> Self {
+        Baseline {
+            last_stat: Mutex::new( (cpu_times, Instant::now()) ),
+        }
+    }
+
+    /// return the cpu times and the real time elapsed from last invoke.
+    ///
+    /// `read` is called with the lock held, so that concurrent samples are ordered by their
+    /// readings and a newer baseline is never replaced by an older one.
+    fn sample<F>(&self, read: F) -> crate::Result<(CpuTimes, Duration)>
+    where
+        F: FnOnce() -> crate::Result<CpuTimes>,
+    {
+        let mut last_stat =
+            self.last_stat.lock()
+            .unwrap_or_else(|e| { e.into_inner() });
+
+        let cpu_times = read()?;
+        let now = Instant::now();
+
+        let (old_cpu_times, old_now) =
+            core::mem::replace(
+                &mut *last_stat,
+                (cpu_times, now)
+            );
+
+        Ok((
+            cpu_times.saturating_sub(&old_cpu_times),
+            now.saturating_duration_since(old_now),
+        ))
+    }
+}
+
+/// A thread-safe struct to monitor process cpu usage.
+#[derive(Debug)]
+pub struct SyncProcessStat {
+    pid: u32,
+    baseline: Baseline,
+    normalization: Normalization,
+
+    /// the divisor of `normalization`, resolved by `with_normalization` and `refresh_limit`.
+    divisor: Mutex<f64>,
+}
+
+impl SyncProcessStat {
+    /// return a monitor of current process.
+    pub fn current() -> crate::Result<Self> {
+        Self::new(std::process::id(), Normalization::None, 1.0)
+    }
+
+    /// return a monitor of the process specified by `pid`.
+    ///
+    /// monitoring fails with an error once that process exited.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        Self::new(pid, Normalization::None, 1.0)
+    }
+
+    fn new(pid: u32, normalization: Normalization, divisor: f64) -> crate::Result<Self> {
+        Ok(SyncProcessStat {
+            pid,
+            baseline: Baseline::new( process_cpu_times(pid)? ),
+            normalization,
+            divisor: Mutex::new(divisor),
+        })
+    }
+
+    /// return a new monitor of the same process with the same normalization,
+    /// whose baseline is independent of this one and starts from now.
+    pub fn reader(&self) -> crate::Result<Self> {
+        Self::new(self.pid, self.normalization, self.divisor())
+    }
+
+    /// make the returning of `cpu` and `cpu_usages` normalized.
+    ///
+    /// the cpu limit is resolved once here, call [`SyncProcessStat::refresh_limit`] if it may change.
+    pub fn with_normalization(mut self, normalization: Normalization) -> crate::Result<Self> {
+        self.divisor = Mutex::new( normalization.divisor(self.pid)? );
+        self.normalization = normalization;
+        Ok(self)
+    }
+
+    /// resolve the cpu limit of normalization again, e.g. after the cpu quota of container is updated.
+    ///
+    /// the readers taken before are not affected.
+    pub fn refresh_limit(&self) -> crate::Result<()> {
+        let divisor = self.normalization.divisor(self.pid)?;
+        *self.divisor.lock().unwrap_or_else(|e| { e.into_inner() }) = divisor;
+        Ok(())
+    }
+
+    fn divisor(&self) -> f64 {
+        *self.divisor.lock()
+            .unwrap_or_else(|e| { e.into_inner() })
+    }
+
+    /// return the pid of monitored process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    fn sample(&self) -> crate::Result<(CpuTimes, Duration)> {
+        self.baseline.sample(|| { process_cpu_times(self.pid) })
+    }
+
+    /// return the cpu usage from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu(&self) -> crate::Result<f64> {
+        Ok( self.cpu_usages()?.total() )
+    }
+
+    /// return the cpu usage in user mode and system mode respectively from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_usages(&self) -> crate::Result<CpuUsages> {
+        let (cpu_times, real_time) = self.sample()?;
+        let usages = cpu_times.usages(real_time);
+
+        let divisor = self.divisor();
+        Ok(CpuUsages {
+            user: usages.user / divisor,
+            system: usages.system / divisor,
+        })
+    }
+
+    /// return the cpu time in user mode and system mode respectively from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        Ok( self.sample()?.0 )
+    }
+}
+
+/// A thread-safe struct to monitor thread cpu usage.
+///
+/// Like [`ThreadStat`](super::ThreadStat), the cpu time is read from the thread cpu-time clock,
+/// and the user and system split is read only by `cpu_usages` and `cpu_times`.
+#[derive(Debug)]
+pub struct SyncThreadStat {
+    tid: ThreadId,
+    baseline: Mutex<ThreadBaseline>,
+}
+
+impl TryFrom<ThreadId> for SyncThreadStat {
+    type Error = crate::Error;
+
+    fn try_from(tid: ThreadId) -> crate::Result<Self> {
+        Ok(SyncThreadStat {
+            tid,
+            baseline: Mutex::new( ThreadBaseline::new(tid)? ),
+        })
+    }
+}
+
+impl SyncThreadStat {
+    /// return a monitor of current thread.
+    pub fn current() -> crate::Result<Self> {
+        ThreadId::current().try_into()
+    }
+
+    /// return a new monitor of the same thread,
+    /// whose baseline is independent of this one and starts from now.
+    pub fn reader(&self) -> crate::Result<Self> {
+        self.tid.try_into()
+    }
+
+    /// return the thread id of monitored thread.
+    pub fn tid(&self) -> ThreadId {
+        self.tid
+    }
+
+    fn baseline(&self) -> std::sync::MutexGuard<'_, ThreadBaseline> {
+        self.baseline.lock()
+            .unwrap_or_else(|e| { e.into_inner() })
+    }
+
+    /// return the cpu usage from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_usage(&self) -> crate::Result<f64> {
+        let (cpu_time, real_time) = self.baseline().sample(self.tid)?;
+        let real_time = real_time.as_secs_f64();
+        if real_time <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok( cpu_time.as_secs_f64() / real_time )
+    }
+
+    /// return the cpu usage in user mode and system mode respectively from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_usages(&self) -> crate::Result<CpuUsages> {
+        let (cpu_times, real_time) = self.baseline().sample_times(self.tid)?;
+        Ok( cpu_times.usages(real_time) )
+    }
+
+    /// return the cpu time from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_time(&self) -> crate::Result<Duration> {
+        Ok( self.baseline().sample(self.tid)?.0 )
+    }
+
+    /// return the cpu time in user mode and system mode respectively from last invoke of any thread,
+    /// or when this struct created if it is the first invoke.
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        Ok( self.baseline().sample_times(self.tid)?.0 )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    fn assert_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_sync_stat() {
+        assert_sync::<SyncProcessStat>();
+        assert_sync::<SyncThreadStat>();
+
+        let before = ThreadId::current().cpu_time().unwrap();
+        let thread = Arc::new(SyncThreadStat::current().unwrap());
+        let reader = thread.reader().unwrap();
+
+        let _ = (0..10_000_000u64).map(std::hint::black_box).sum::<u64>();
+
+        // sampled from another thread, it moves the baseline of `thread` only.
+        let shared = thread.clone();
+        let first =
+            std::thread::spawn(move || { shared.cpu_time().unwrap() })
+            .join()
+            .unwrap();
+        assert!(first > Duration::ZERO);
+
+        let again = thread.cpu_time().unwrap();
+        assert!(again < first);
+        assert!(reader.cpu_time().unwrap() >= first);
+
+        // the same clock is read from any thread, so no more than the consumed cpu time is counted.
+        let after = ThreadId::current().cpu_time().unwrap();
+        assert!(first + again <= after - before);
+
+        let process = SyncProcessStat::current().unwrap();
+        let usage = process.reader().unwrap().cpu().unwrap();
+        assert!(usage >= 0.0);
+    }
+
+    #[test]
+    fn test_concurrent_samples() {
+        // readings increase by one nanosecond each, so the deltas add up exactly
+        // only if no baseline is replaced by an older reading.
+        let baseline = Arc::new(Baseline::new(CpuTimes::default()));
+        let clock = Arc::new(std::sync::atomic::AtomicU64::new(0));
+
+        let handles: Vec<_> =
+            (0..4)
+            .map(|_| {
+                let baseline = baseline.clone();
+                let clock = clock.clone();
+                std::thread::spawn(move || {
+                    let mut sum = Duration::ZERO;
+                    for _ in 0..10_000 {
+                        let (cpu_times, _) =
+                            baseline.sample(|| {
+                                let nanos = clock.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                                Ok(CpuTimes {
+                                    user: Duration::from_nanos(nanos),
+                                    system: Duration::ZERO,
+                                })
+                            })
+                            .unwrap();
+                        assert_eq!(cpu_times.user, Duration::from_nanos(1));
+                        sum += cpu_times.user;
+                    }
+                    sum
+                })
+            })
+            .collect();
+
+        let sum: Duration = handles.into_iter().map(|h| { h.join().unwrap() }).sum();
+        assert_eq!(sum, Duration::from_nanos(40_000));
+
+        // the same holds for a real monitor, where no cpu time is counted twice.
+        let before = process_cpu_times(std::process::id()).unwrap();
+        let process = Arc::new(SyncProcessStat::current().unwrap());
+        let handles: Vec<_> =
+            (0..4)
+            .map(|_| {
+                let process = process.clone();
+                std::thread::spawn(move || {
+                    (0..1000)
+                        .map(|_| { process.cpu_times().unwrap().total() })
+                        .sum::<Duration>()
+                })
+            })
+            .collect();
+
+        let sum: Duration = handles.into_iter().map(|h| { h.join().unwrap() }).sum();
+        let after = process_cpu_times(std::process::id()).unwrap();
+        assert!(sum <= after.saturating_sub(&before).total());
+    }
+}
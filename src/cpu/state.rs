@@ -0,0 +1,145 @@
+//! Scheduling state of threads, read from the `state` field of
+//! [/proc/{pid}/task/{tid}/stat](https://man7.org/linux/man-pages/man5/proc.5.html).
+//!
+//! A hanging process with threads in [`ThreadState::Uninterruptible`] is blocked
+//! on io (or a kernel lock), while one with all threads sleeping is just idle.
+//!
+//! ```
+//! # use perfmon::cpu::ThreadStates;
+//! let states = ThreadStates::current().unwrap();
+//! println!("{} threads blocked on io", states.uninterruptible);
+//! ```
+
+use crate::utils::process::get_process;
+
+/// The scheduling state of a thread.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ThreadState {
+    /// `R`, running or runnable.
+    Running,
+
+    /// `S`, interruptible sleep, e.g. waiting for an event or a lock.
+    Sleeping,
+
+    /// `D`, uninterruptible sleep, usually waiting for io.
+    Uninterruptible,
+
+    /// `T`, stopped by a signal.
+    Stopped,
+
+    /// `t`, stopped by a debugger.
+    Traced,
+
+    /// `Z`, exited but not yet reaped.
+    Zombie,
+
+    /// `X`, dead.
+    Dead,
+
+    /// `I`, an idle kernel thread.
+    Idle,
+
+    /// `P`, parked.
+    Parked,
+
+    /// a state not listed above, e.g. those of old kernels.
+    Other(char),
+}
+
+impl From<char> for ThreadState {
+    fn from(state: char) -> ThreadState {
+        match state {
+            'R' => ThreadState::Running,
+            'S' => ThreadState::Sleeping,
+            'D' => ThreadState::Uninterruptible,
+            'T' => ThreadState::Stopped,
+            't' => ThreadState::Traced,
+            'Z' => ThreadState::Zombie,
+            'X' | 'x' => ThreadState::Dead,
+            'I' => ThreadState::Idle,
+            'P' => ThreadState::Parked,
+            c => ThreadState::Other(c),
+        }
+    }
+}
+
+/// The number of threads in each state of a process.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ThreadStates {
+    pub running: usize,
+    pub sleeping: usize,
+    pub uninterruptible: usize,
+
+    /// stopped by a signal or a debugger.
+    pub stopped: usize,
+
+    pub zombie: usize,
+
+    /// idle, parked, dead or unknown.
+    pub other: usize,
+}
+
+impl ThreadStates {
+    /// count the threads of current process.
+    pub fn current() -> crate::Result<Self> {
+        Self::for_pid(std::process::id())
+    }
+
+    /// count the threads of the process specified by `pid`.
+    ///
+    /// threads that exit while `/proc/{pid}/task` is being walked are skipped.
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        let mut states = ThreadStates::default();
+        for task in get_process(pid)?.tasks()? {
+            let Ok(task) = task else { continue };
+            let Ok(stat) = task.stat() else { continue };
+            states.add(stat.state.into());
+        }
+        Ok(states)
+    }
+
+    fn add(&mut self, state: ThreadState) {
+        let count =
+            match state {
+                ThreadState::Running => &mut self.running,
+                ThreadState::Sleeping => &mut self.sleeping,
+                ThreadState::Uninterruptible => &mut self.uninterruptible,
+                ThreadState::Stopped | ThreadState::Traced => &mut self.stopped,
+                ThreadState::Zombie => &mut self.zombie,
+                _ => &mut self.other,
+            };
+        *count += 1;
+    }
+
+    /// the number of threads counted.
+    pub fn total(&self) -> usize {
+        self.running + self.sleeping + self.uninterruptible
+        + self.stopped + self.zombie + self.other
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::ThreadStat;
+
+    #[test]
+    fn test_thread_states() {
+        assert_eq!(ThreadState::from('D'), ThreadState::Uninterruptible);
+        assert_eq!(ThreadState::from('W'), ThreadState::Other('W'));
+
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || { rx.recv().ok() });
+
+        let states = ThreadStates::current().unwrap();
+        // the test thread itself is running.
+        assert!(states.running >= 1);
+        assert!(states.total() >= 2);
+
+        let stat = ThreadStat::current().unwrap();
+        assert_eq!(stat.state().unwrap(), ThreadState::Running);
+
+        drop(tx);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+//! System-wide cpu usage, read from [/proc/stat](https://man7.org/linux/man-pages/man5/proc.5.html).
+//!
+//! ```
+//! # use perfmon::cpu::SystemCpuStat;
+//! let stat = SystemCpuStat::new().unwrap();
+//! std::thread::sleep(std::time::Duration::from_millis(100));
+//! let usage = stat.cpu().unwrap();
+//! println!("busy: {:.2}%, steal: {:.2}%", usage.all.busy() * 100f64, usage.all.steal * 100f64);
+//! ```
+
+use crate::Error;
+
+use core::cell::Cell;
+
+/// cumulative ticks spent in each state, a line of `/proc/stat`.
+///
+/// `guest` and `guest_nice` are already counted in `user` and `nice`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+struct CpuTicks {
+    user: u64,
+    nice: u64,
+    system: u64,
+    idle: u64,
+    iowait: u64,
+    irq: u64,
+    softirq: u64,
+    steal: u64,
+}
+
+impl CpuTicks {
+    fn parse(fields: &[&str]) -> Option<Self> {
+        let get = |i: usize| -> Option<u64> {
+            match fields.get(i) {
+                // missing on old kernels.
+                None => Some(0),
+                Some(v) => v.parse().ok(),
+            }
+        };
+
+        Some(CpuTicks {
+            user: get(0)?,
+            nice: get(1)?,
+            system: get(2)?,
+            idle: get(3)?,
+            iowait: get(4)?,
+            irq: get(5)?,
+            softirq: get(6)?,
+            steal: get(7)?,
+        })
+    }
+
+    fn saturating_sub(&self, rhs: &Self) -> Self {
+        CpuTicks {
+            user: self.user.saturating_sub(rhs.user),
+            nice: self.nice.saturating_sub(rhs.nice),
+            system: self.system.saturating_sub(rhs.system),
+            idle: self.idle.saturating_sub(rhs.idle),
+            iowait: self.iowait.saturating_sub(rhs.iowait),
+            irq: self.irq.saturating_sub(rhs.irq),
+            softirq: self.softirq.saturating_sub(rhs.softirq),
+            steal: self.steal.saturating_sub(rhs.steal),
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.user + self.nice + self.system + self.idle
+        + self.iowait + self.irq + self.softirq + self.steal
+    }
+
+    fn usage(&self) -> CpuStateUsage {
+        let total = self.total();
+        if total == 0 {
+            return CpuStateUsage::default();
+        }
+
+        let ratio = |ticks: u64| { ticks as f64 / total as f64 };
+        CpuStateUsage {
+            user: ratio(self.user),
+            nice: ratio(self.nice),
+            system: ratio(self.system),
+            idle: ratio(self.idle),
+            iowait: ratio(self.iowait),
+            irq: ratio(self.irq),
+            softirq: ratio(self.softirq),
+            steal: ratio(self.steal),
+        }
+    }
+}
+
+/// the fraction of time a cpu spent in each state, they sum up to 1.0.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CpuStateUsage {
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+
+    /// the time stolen by hypervisor for other virtual machines.
+    pub steal: f64,
+}
+
+impl CpuStateUsage {
+    /// the fraction of time not in `idle` and `iowait`.
+    pub fn busy(&self) -> f64 {
+        (1.0 - self.idle - self.iowait).max(0.0)
+    }
+}
+
+/// cpu usage of a single cpu core.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CoreCpuUsage {
+    /// the cpu number, `N` of `cpuN` line in `/proc/stat`.
+    pub cpu: usize,
+    pub usage: CpuStateUsage,
+}
+
+/// system-wide cpu usage between two samples.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemCpuUsage {
+    /// aggregated over all cpu cores, it is normalized.
+    pub all: CpuStateUsage,
+
+    /// cores that are online in both samples.
+    pub cores: Vec<CoreCpuUsage>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ProcStat {
+    all: CpuTicks,
+    cores: Vec<(usize, CpuTicks)>,
+}
+
+fn read_proc_stat() -> crate::Result<ProcStat> {
+    let content = std::fs::read_to_string("/proc/stat")?;
+
+    let mut stat = ProcStat::default();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next() else { continue };
+        let Some(cpu) = name.strip_prefix("cpu") else { continue };
+
+        let fields: Vec<&str> = fields.collect();
+        let Some(ticks) = CpuTicks::parse(&fields) else {
+            return Err( Error::Parse(format!("/proc/stat line: {:?}", line)) );
+        };
+
+        if cpu.is_empty() {
+            stat.all = ticks;
+        } else {
+            stat.cores.push( (cpu.parse()?, ticks) );
+        }
+    }
+    Ok(stat)
+}
+
+/// A struct to monitor system-wide cpu usage.
+pub struct SystemCpuStat {
+    last_stat: Cell<ProcStat>,
+}
+
+impl SystemCpuStat {
+    /// return a monitor of system-wide cpu usage.
+    pub fn new() -> crate::Result<Self> {
+        Ok(SystemCpuStat {
+            last_stat: Cell::new( read_proc_stat()? ),
+        })
+    }
+
+    /// return the cpu usage from last invoke,
+    /// or when this struct created if it is the first invoke.
+    ///
+    /// the precision is limited by kernel ticks (usually 10ms), so it returns
+    /// all zero if called again too soon.
+    pub fn cpu(&self) -> crate::Result<SystemCpuUsage> {
+        let stat = read_proc_stat()?;
+        let old_stat = self.last_stat.replace( stat.clone() );
+
+        let cores =
+            stat.cores.iter()
+            .filter_map(|(cpu, ticks)| {
+                let (_, old_ticks) =
+                    old_stat.cores.iter().find(|(old_cpu, _)| { old_cpu == cpu })?;
+
+                Some(CoreCpuUsage {
+                    cpu: *cpu,
+                    usage: ticks.saturating_sub(old_ticks).usage(),
+                })
+            })
+            .collect();
+
+        Ok(SystemCpuUsage {
+            all: stat.all.saturating_sub(&old_stat.all).usage(),
+            cores,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_system_cpu_usage() {
+        let stat = SystemCpuStat::new().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let usage = stat.cpu().unwrap();
+
+        let all = usage.all;
+        let sum =
+            all.user + all.nice + all.system + all.idle
+            + all.iowait + all.irq + all.softirq + all.steal;
+        assert!((sum - 1.0).abs() < 1e-6);
+        assert!(all.busy() <= 1.0);
+
+        assert!(! usage.cores.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! Enumerate every thread of current process with its cpu usage.
+//!
+//! ```
+//! # use perfmon::cpu::ProcessThreads;
+//! let threads = ProcessThreads::current().unwrap();
+//! let _ = (0..1_000_000).into_iter().sum::<u64>();
+//! for t in threads.sample().unwrap() {
+//!     println!("{:?} {} {:.2}%", t.tid, t.name, t.cpu_usage * 100f64);
+//! }
+//! ```
+
+use super::{platform, ThreadId};
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Whether a thread was seen by both samples, or only one of them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ThreadPresence {
+    /// the thread exists in both the previous and the current sample.
+    Existing,
+
+    /// the thread is spawned after the previous sample.
+    Appeared,
+
+    /// the thread exited after the previous sample.
+    /// its cpu time consumed after the previous sample is unknown.
+    Disappeared,
+}
+
+/// cpu usage of a single thread between two samples.
+#[derive(Debug, Clone)]
+pub struct ThreadUsage {
+    pub tid: ThreadId,
+
+    /// the name of thread (truncated by OS).
+    pub name: String,
+
+    /// un-normalized cpu usage, see [`ThreadStat::cpu_usage`](super::ThreadStat::cpu_usage).
+    pub cpu_usage: f64,
+
+    /// the cpu time in user mode and system mode between two samples.
+    pub cpu_time: Duration,
+
+    pub presence: ThreadPresence,
+}
+
+/// name and cpu time of each thread, keyed by tid.
+type ThreadsCputime = HashMap<ThreadId, (String, Duration)>;
+
+/// A struct to monitor cpu usage of all threads in current process.
+pub struct ProcessThreads {
+    last_stat: Cell<(ThreadsCputime, Instant)>,
+}
+
+fn capture() -> crate::Result<ThreadsCputime> {
+    let threads = platform::threads_cputime()?;
+    Ok(
+        threads.into_iter()
+        .map(|(tid, name, cputime)| { (tid, (name, cputime)) })
+        .collect()
+    )
+}
+
+impl ProcessThreads {
+    /// return a monitor of all threads in current process.
+    pub fn current() -> crate::Result<Self> {
+        let threads = capture()?;
+        let now = Instant::now();
+        Ok(ProcessThreads {
+            last_stat: Cell::new( (threads, now) ),
+        })
+    }
+
+    /// return the cpu usage of every thread from last invoke,
+    /// or when this struct created if it is the first invoke.
+    ///
+    /// the returning list is sorted by cpu usage, from highest to lowest.
+    pub fn sample(&self) -> crate::Result<Vec<ThreadUsage>> {
+        let threads = capture()?;
+        let now = Instant::now();
+
+        let (mut old_threads, old_now) =
+            self.last_stat.replace(
+                (threads.clone(), now)
+            );
+
+        let real_time: f64 =
+            now.saturating_duration_since(old_now)
+            .as_secs_f64();
+
+        let mut usages = Vec::with_capacity(threads.len());
+        for (tid, (name, cputime)) in threads {
+            // a thread appeared after last sample has consumed all of its cpu time since then.
+            let (old_cputime, presence) =
+                match old_threads.remove(&tid) {
+                    Some((_, old_cputime)) => (old_cputime, ThreadPresence::Existing),
+                    None => (Duration::ZERO, ThreadPresence::Appeared),
+                };
+
+            let cpu_time = cputime.saturating_sub(old_cputime);
+            let cpu_usage =
+                if real_time > 0.0 {
+                    cpu_time.as_secs_f64() / real_time
+                } else {
+                    0.0
+                };
+
+            usages.push(ThreadUsage {
+                tid,
+                name,
+                cpu_usage,
+                cpu_time,
+                presence,
+            });
+        }
+
+        for (tid, (name, _)) in old_threads {
+            usages.push(ThreadUsage {
+                tid,
+                name,
+                cpu_usage: 0.0,
+                cpu_time: Duration::ZERO,
+                presence: ThreadPresence::Disappeared,
+            });
+        }
+
+        usages.sort_by(|a, b| {
+            b.cpu_usage.total_cmp(&a.cpu_usage)
+            .then(i32::from(a.tid).cmp(&i32::from(b.tid)))
+        });
+        Ok(usages)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_process_threads() {
+        let threads = ProcessThreads::current().unwrap();
+
+        let (tid_tx, tid_rx) = std::sync::mpsc::channel();
+        let (exit_tx, exit_rx) = std::sync::mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            let mut x = 0u64;
+            for i in 0..100_000_000u64 {
+                x = std::hint::black_box(x.wrapping_add(i));
+            }
+            tid_tx.send(ThreadId::current()).unwrap();
+            exit_rx.recv().unwrap();
+        });
+        let busy = tid_rx.recv().unwrap();
+
+        let usages = threads.sample().unwrap();
+        assert!(usages.windows(2).all(|w| w[0].cpu_usage >= w[1].cpu_usage));
+
+        let me = ThreadId::current();
+        assert!(usages.iter().any(|t| {
+            t.tid == me && t.presence == ThreadPresence::Existing
+        }));
+        assert!(usages.iter().any(|t| {
+            t.tid == busy
+            && t.presence == ThreadPresence::Appeared
+            && t.cpu_time > Duration::ZERO
+        }));
+
+        exit_tx.send(()).unwrap();
+        handle.join().unwrap();
+
+        let usages = threads.sample().unwrap();
+        assert!(usages.iter().any(|t| {
+            t.tid == busy && t.presence == ThreadPresence::Disappeared
+        }));
+    }
+}
@@ -0,0 +1,224 @@
+//! Cpu usage and CFS throttling of the cgroup current process belongs to.
+//!
+//! When a cgroup runs out of its cpu quota in a period, all of its threads
+//! are throttled until the next period, which looks like idle to
+//! [`ProcessStat`](super::ProcessStat) but is actually starved.
+//!
+//! | cgroup | throttling | usage |
+//! | -- | -- | -- |
+//! | v1 | `cpu.stat` of cpu controller | `cpuacct.usage` of cpuacct controller |
+//! | v2 | `cpu.stat` | `cpu.stat` |
+//!
+//! ```
+//! # use perfmon::cpu::CgroupCpuStat;
+//! if let Some(stat) = CgroupCpuStat::current().unwrap() {
+//!     std::thread::sleep(std::time::Duration::from_millis(100));
+//!     let delta = stat.sample().unwrap();
+//!     println!("throttled in {:.2}% periods", delta.throttled_ratio() * 100f64);
+//! }
+//! ```
+
+use crate::utils::cgroup::{Cgroup, CgroupVersion};
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use std::time::Instant;
+
+/// cumulative cpu counters of a cgroup.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CgroupCpuCounters {
+    /// the cpu time consumed by all tasks in the cgroup, `None` if unavailable.
+    pub usage: Option<Duration>,
+
+    /// the number of enforcement periods that have elapsed.
+    pub nr_periods: u64,
+
+    /// the number of periods the cgroup has been throttled in.
+    pub nr_throttled: u64,
+
+    /// the total time the cgroup has been throttled.
+    pub throttled_time: Duration,
+}
+
+impl CgroupCpuCounters {
+    pub fn saturating_sub(&self, rhs: &CgroupCpuCounters) -> CgroupCpuCounters {
+        CgroupCpuCounters {
+            usage:
+                self.usage.zip(rhs.usage)
+                .map(|(a, b)| { a.saturating_sub(b) }),
+            nr_periods: self.nr_periods.saturating_sub(rhs.nr_periods),
+            nr_throttled: self.nr_throttled.saturating_sub(rhs.nr_throttled),
+            throttled_time: self.throttled_time.saturating_sub(rhs.throttled_time),
+        }
+    }
+}
+
+/// cgroup cpu counters between two samples.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CgroupCpuDelta {
+    pub counters: CgroupCpuCounters,
+
+    /// the real time elapsed between two samples.
+    pub elapsed: Duration,
+}
+
+impl CgroupCpuDelta {
+    /// the cpu usage of the cgroup, un-normalized.
+    pub fn cpu_usage(&self) -> Option<f64> {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return Some(0.0);
+        }
+        self.counters.usage.map(|usage| { usage.as_secs_f64() / elapsed })
+    }
+
+    /// the fraction of periods in which the cgroup has been throttled.
+    pub fn throttled_ratio(&self) -> f64 {
+        if self.counters.nr_periods == 0 {
+            return 0.0;
+        }
+        self.counters.nr_throttled as f64 / self.counters.nr_periods as f64
+    }
+
+    /// the throttled time divided by real time.
+    pub fn throttled_usage(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        self.counters.throttled_time.as_secs_f64() / elapsed
+    }
+}
+
+/// parse the flat keyed `cpu.stat`, missing keys (e.g. in root cgroup) are zero.
+fn parse_cpu_stat(content: &str) -> impl Fn(&str) -> u64 + '_ {
+    move |name| {
+        content.lines()
+            .find_map(|line| {
+                let (key, value) = line.split_once(' ')?;
+                if key != name {
+                    return None;
+                }
+                value.trim().parse().ok()
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Source {
+    cpu: Cgroup,
+
+    /// the cgroup of cpuacct controller, only for cgroup v1.
+    cpuacct: Option<Cgroup>,
+}
+
+impl Source {
+    fn read_counters(&self) -> crate::Result<CgroupCpuCounters> {
+        let content = self.cpu.read("cpu.stat")?;
+        let get = parse_cpu_stat(&content);
+
+        match self.cpu.version {
+            CgroupVersion::V1 => {
+                let usage =
+                    self.cpuacct.as_ref()
+                    .and_then(|c| { c.read("cpuacct.usage").ok() })
+                    .and_then(|u| { u.trim().parse().ok() })
+                    .map(Duration::from_nanos);
+
+                Ok(CgroupCpuCounters {
+                    usage,
+                    nr_periods: get("nr_periods"),
+                    nr_throttled: get("nr_throttled"),
+                    throttled_time: Duration::from_nanos(get("throttled_time")),
+                })
+            }
+            CgroupVersion::V2 => {
+                Ok(CgroupCpuCounters {
+                    usage: Some( Duration::from_micros(get("usage_usec")) ),
+                    nr_periods: get("nr_periods"),
+                    nr_throttled: get("nr_throttled"),
+                    throttled_time: Duration::from_micros(get("throttled_usec")),
+                })
+            }
+        }
+    }
+}
+
+/// A struct to monitor the cpu usage and throttling of the cgroup current process belongs to.
+pub struct CgroupCpuStat {
+    source: Source,
+    last_stat: Cell<(CgroupCpuCounters, Instant)>,
+}
+
+impl CgroupCpuStat {
+    /// return a monitor of the cgroup current process belongs to,
+    /// located from `/proc/self/cgroup`.
+    ///
+    /// `None` if the cpu controller is not available.
+    pub fn current() -> crate::Result<Option<Self>> {
+        let Some(cpu) = Cgroup::of_controller("cpu")? else {
+            return Ok(None);
+        };
+        let cpuacct =
+            match cpu.version {
+                CgroupVersion::V1 => Cgroup::of_controller("cpuacct")?,
+                CgroupVersion::V2 => None,
+            };
+
+        let source = Source { cpu, cpuacct };
+        let counters = source.read_counters()?;
+        let now = Instant::now();
+        Ok(Some(CgroupCpuStat {
+            source,
+            last_stat: Cell::new( (counters, now) ),
+        }))
+    }
+
+    /// return the cumulative counters, it does not affect the baseline of `sample`.
+    pub fn counters(&self) -> crate::Result<CgroupCpuCounters> {
+        self.source.read_counters()
+    }
+
+    /// return the counters from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn sample(&self) -> crate::Result<CgroupCpuDelta> {
+        let counters = self.source.read_counters()?;
+        let now = Instant::now();
+
+        let (old_counters, old_now) =
+            self.last_stat.replace(
+                (counters, now)
+            );
+
+        Ok(CgroupCpuDelta {
+            counters: counters.saturating_sub(&old_counters),
+            elapsed: now.saturating_duration_since(old_now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_cpu_stat() {
+        let content = "usage_usec 739978754\nnr_periods 10\nnr_throttled 3\nthrottled_usec 1500\n";
+        let get = parse_cpu_stat(content);
+        assert_eq!(get("usage_usec"), 739978754);
+        assert_eq!(get("nr_throttled"), 3);
+        assert_eq!(get("nr_bursts"), 0);
+
+        let Some(stat) = CgroupCpuStat::current().unwrap() else { return };
+        let _ = (0..10_000_000).into_iter().sum::<u64>();
+        let delta = stat.sample().unwrap();
+        assert!(delta.counters.nr_throttled <= delta.counters.nr_periods);
+        assert!(delta.throttled_ratio() <= 1.0);
+        assert!(delta.elapsed > Duration::ZERO);
+        if let Some(usage) = delta.cpu_usage() {
+            assert!(usage >= 0.0);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Cpu topology read from [/sys/devices/system/cpu](https://www.kernel.org/doc/Documentation/ABI/stable/sysfs-devices-system-cpu)
+//! and `/proc/cpuinfo`.
+//!
+//! Unlike [`processor_numbers`](super::processor_numbers), it tells the cpus
+//! that are offline (e.g. sleeping cores of ARM devices for battery saving),
+//! and which logical cpus share a physical core.
+//!
+//! ```
+//! # use perfmon::cpu::CpuTopology;
+//! let topology = CpuTopology::current().unwrap();
+//! println!(
+//!     "{} sockets, {} physical cores, {} logical cpus online",
+//!     topology.sockets(),
+//!     topology.physical_cores(),
+//!     topology.online.len(),
+//! );
+//! ```
+
+use super::platform::CPU_INFO;
+use crate::utils::sysfs::{parse_cpu_list, read_value};
+use crate::Error;
+
+use std::path::PathBuf;
+
+const SYS_CPU: &str = "/sys/devices/system/cpu";
+
+/// A logical cpu that is online.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogicalCpu {
+    /// the cpu number, `N` of `/sys/devices/system/cpu/cpuN`.
+    pub cpu: usize,
+
+    /// the socket this cpu belongs to.
+    pub package_id: Option<usize>,
+
+    /// the physical core this cpu belongs to, unique in a socket.
+    pub core_id: Option<usize>,
+
+    /// the logical cpus sharing the same physical core (SMT siblings), including this cpu.
+    pub thread_siblings: Vec<usize>,
+
+    /// the `model name` in `/proc/cpuinfo`, it is unavailable on most ARM devices.
+    pub model_name: Option<String>,
+
+    /// the relative compute capacity (max 1024), differs between big and LITTLE cores.
+    pub capacity: Option<u64>,
+
+    /// current frequency in kHz.
+    pub cur_freq_khz: Option<u64>,
+
+    /// max frequency in kHz.
+    pub max_freq_khz: Option<u64>,
+}
+
+/// The cpu topology of this machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuTopology {
+    /// cpus that can be brought online, including those hot-pluggable.
+    pub possible: Vec<usize>,
+
+    /// cpus that are online and being scheduled.
+    pub online: Vec<usize>,
+
+    /// cpus that are possible but offline.
+    pub offline: Vec<usize>,
+
+    /// details of each online cpu.
+    pub cpus: Vec<LogicalCpu>,
+}
+
+fn read_cpu_list(file: &str) -> Option<Vec<usize>> {
+    let list = std::fs::read_to_string(format!("{}/{}", SYS_CPU, file)).ok()?;
+    parse_cpu_list(&list)
+}
+
+fn model_name(cpu: usize) -> Option<String> {
+    let info = CPU_INFO.as_ref()?;
+    (0..info.num_cores())
+        .find(|&i| {
+            info.get_field(i, "processor")
+            .and_then(|p| { p.parse::<usize>().ok() })
+            == Some(cpu)
+        })
+        .and_then(|i| { info.model_name(i) })
+        .map(|name| { name.to_string() })
+}
+
+fn logical_cpu(cpu: usize) -> LogicalCpu {
+    let dir = PathBuf::from(format!("{}/cpu{}", SYS_CPU, cpu));
+
+    let thread_siblings =
+        ["topology/core_cpus_list", "topology/thread_siblings_list"]
+        .iter()
+        .find_map(|file| {
+            let list = std::fs::read_to_string(dir.join(file)).ok()?;
+            parse_cpu_list(&list)
+        })
+        .unwrap_or_else(|| { vec![cpu] });
+
+    LogicalCpu {
+        cpu,
+        package_id: read_value(dir.join("topology/physical_package_id")),
+        core_id: read_value(dir.join("topology/core_id")),
+        thread_siblings,
+        model_name: model_name(cpu),
+        capacity: read_value(dir.join("cpu_capacity")),
+        cur_freq_khz: read_value(dir.join("cpufreq/scaling_cur_freq")),
+        max_freq_khz: read_value(dir.join("cpufreq/cpuinfo_max_freq")),
+    }
+}
+
+impl CpuTopology {
+    /// read the cpu topology of this machine.
+    pub fn current() -> crate::Result<Self> {
+        let Some(online) = read_cpu_list("online") else {
+            return Err( Error::unsupported(format!("cpu topology without {}/online", SYS_CPU)) );
+        };
+        let possible = read_cpu_list("possible").unwrap_or_else(|| { online.clone() });
+        let offline =
+            possible.iter()
+            .copied()
+            .filter(|cpu| { ! online.contains(cpu) })
+            .collect();
+
+        let cpus = online.iter().map(|&cpu| { logical_cpu(cpu) }).collect();
+
+        Ok(CpuTopology {
+            possible,
+            online,
+            offline,
+            cpus,
+        })
+    }
+
+    /// the number of sockets that have online cpus.
+    pub fn sockets(&self) -> usize {
+        let mut packages: Vec<Option<usize>> =
+            self.cpus.iter().map(|c| { c.package_id }).collect();
+        packages.sort_unstable();
+        packages.dedup();
+        packages.len()
+    }
+
+    /// the number of physical cores that have online cpus.
+    pub fn physical_cores(&self) -> usize {
+        let mut cores: Vec<usize> =
+            self.cpus.iter()
+            .map(|c| { c.thread_siblings.iter().copied().min().unwrap_or(c.cpu) })
+            .collect();
+        cores.sort_unstable();
+        cores.dedup();
+        cores.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cpu_topology() {
+        let topology = CpuTopology::current().unwrap();
+
+        assert!(! topology.online.is_empty());
+        assert!(topology.online.iter().all(|cpu| { topology.possible.contains(cpu) }));
+        assert_eq!(topology.cpus.len(), topology.online.len());
+        assert!(topology.sockets() >= 1);
+        assert!(topology.physical_cores() >= topology.sockets());
+        assert!(topology.physical_cores() <= topology.online.len());
+    }
+}
@@ -1,4 +1,4 @@
-use super::processor_numbers;
+use super::{processor_numbers, CpuTimes, CpuUsages};
 use super::windows::process_times::ProcessTimes;
 use super::windows::system_times::SystemTimes;
 use super::windows::thread_times::ThreadTimes;
@@ -17,7 +17,7 @@ pub mod process_times;
 pub mod system_times;
 pub mod thread_times;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ThreadId(u32);
 
 impl ThreadId {
@@ -25,6 +25,31 @@ impl ThreadId {
     pub fn current() -> Self {
         ThreadId(unsafe { GetCurrentThreadId() })
     }
+
+    pub fn name(&self) -> crate::Result<String> {
+        Err( crate::Error::unsupported("thread name on windows") )
+    }
+
+    /// return the cpu time this thread consumed since it started.
+    pub fn cpu_time(&self) -> crate::Result<Duration> {
+        Ok( self.cpu_times()?.total() )
+    }
+
+    /// return the cpu time this thread consumed since it started,
+    /// split into user mode and system mode.
+    pub fn cpu_times(&self) -> crate::Result<CpuTimes> {
+        let thread_times =
+            if *self == Self::current() {
+                ThreadTimes::capture_current()?
+            } else {
+                ThreadTimes::capture_with_thread_id(*self)?
+            };
+
+        Ok(CpuTimes {
+            user: ns100_to_duration(filetime_to_ns100(&thread_times.user)),
+            system: ns100_to_duration(filetime_to_ns100(&thread_times.kernel)),
+        })
+    }
 }
 
 /// convert to u64, unit 100 ns
@@ -43,12 +68,13 @@ fn filetime_to_ns100(ft: &FILETIME) -> u64 {
 
 pub struct ThreadStat {
     tid: ThreadId,
-    last_stat: Cell<(u64, u64)>,
+    last_stat: Cell<(CpuTimes, u64)>,
 }
 
 impl ThreadStat {
+    /// return thread cpu times and total system time (unit 100 ns)
     fn get_times(tid: ThreadId)
-        -> Result<(u64, u64)>
+        -> Result<(CpuTimes, u64)>
     {
         let system_times =
             SystemTimes::capture()?;
@@ -56,24 +82,20 @@ impl ThreadStat {
         let thread_times =
             ThreadTimes::capture_with_thread_id(tid)?;
 
-        let work_time =
-            filetime_to_ns100(&thread_times.kernel)
-            + filetime_to_ns100(&thread_times.user);
+        let cpu_times = CpuTimes {
+            user: ns100_to_duration(filetime_to_ns100(&thread_times.user)),
+            system: ns100_to_duration(filetime_to_ns100(&thread_times.kernel)),
+        };
 
         let total_time =
             filetime_to_ns100(&system_times.kernel)
             + filetime_to_ns100(&system_times.user);
 
-        Ok( (work_time, total_time) )
+        Ok( (cpu_times, total_time) )
     }
 
     pub fn current() -> Result<Self> {
-        let tid = ThreadId::current();
-        let times = Self::get_times(tid)?;
-        Ok(ThreadStat {
-            tid,
-            last_stat: Cell::new(times)
-        })
+        Self::build(ThreadId::current())
     }
 
     pub fn build(tid: ThreadId) -> Result<Self> {
@@ -84,65 +106,71 @@ impl ThreadStat {
         })
     }
 
-    pub fn cpu(&self) -> Result<f64> {
-        let (work_time, total_time) =
+    /// return the cpu times and the total system time (unit 100 ns) elapsed from last invoke.
+    fn sample(&self) -> Result<(CpuTimes, u64)> {
+        let (cpu_times, total_time) =
             Self::get_times(self.tid)?;
 
-        let (old_work_time, old_total_time) =
+        let (old_cpu_times, old_total_time) =
             self.last_stat.replace(
-                (work_time, total_time)
+                (cpu_times, total_time)
             );
 
-        let dt_total_time = total_time - old_total_time;
+        Ok((
+            cpu_times.saturating_sub(&old_cpu_times),
+            total_time.saturating_sub(old_total_time),
+        ))
+    }
+
+    pub fn cpu(&self) -> Result<f64> {
+        Ok( self.cpu_usages()?.total() )
+    }
+
+    pub fn cpu_usages(&self) -> Result<CpuUsages> {
+        let (dt_cpu_times, dt_total_time) = self.sample()?;
 
         if dt_total_time == 0 {
-            return Ok(0.0);
+            return Ok(CpuUsages::default());
         }
 
-        let dt_work_time = work_time - old_work_time;
-
+        // the total system time is summed over all processors,
+        // scale it back to real time to make it un-normalized.
         let cpus = processor_numbers()?;
-        Ok(
-            (dt_work_time as f64)/(dt_total_time as f64)
-            * (cpus as f64)
-        )
+        let real_time = ns100_to_duration(dt_total_time / (cpus as u64));
+        Ok( dt_cpu_times.usages(real_time) )
     }
 
     pub fn cpu_time(&self) -> Result<Duration> {
-        let (work_time, total_time) =
-            Self::get_times(self.tid)?;
-
-        let (old_work_time, old_total_time) =
-            self.last_stat.replace(
-                (work_time, total_time)
-            );
-
-        let cpu_time = work_time - old_work_time;
+        Ok( self.sample()?.0.total() )
+    }
 
-        Ok(
-            Duration::from_nanos(cpu_time)
-        )
+    pub fn cpu_times(&self) -> Result<CpuTimes> {
+        Ok( self.sample()?.0 )
     }
 }
 
 #[inline]
-pub fn cpu_time() -> Result<Duration> {
-    let process_times =
-        ProcessTimes::capture_current()?;
-
-    let kt = filetime_to_ns100(&process_times.kernel);
-    let ut = filetime_to_ns100(&process_times.user);
-
-    // convert ns
-    //
+fn ns100_to_duration(ns100: u64) -> Duration {
     // Note: make it ns unit may overflow in some cases.
     // For example, a machine with 128 cores runs for one year.
-    let mut cpu_time = (kt + ut).saturating_mul(100);
+    Duration::from_nanos(ns100.saturating_mul(100))
+}
 
-    // make it un-normalized
-    let cpus = processor_numbers()?;
-    let cpu_time *= (cpus as u64);
+#[inline]
+pub fn cpu_time() -> Result<Duration> {
+    Ok( cpu_times()?.total() )
+}
 
-    Ok( Duration::from_nanos(cpu_time) )
+pub fn cpu_times() -> Result<CpuTimes> {
+    let process_times =
+        ProcessTimes::capture_current()?;
+
+    Ok(CpuTimes {
+        user: ns100_to_duration(filetime_to_ns100(&process_times.user)),
+        system: ns100_to_duration(filetime_to_ns100(&process_times.kernel)),
+    })
 }
 
+pub fn children_cpu_times() -> crate::Result<CpuTimes> {
+    Err( crate::Error::unsupported("cpu time of children on windows") )
+}
@@ -20,7 +20,6 @@ pub struct ThreadTimes {
 }
 
 impl ThreadTimes {
-    #[allow(dead_code)]
     pub fn capture_current() -> Result<Self> {
         unsafe {
             Self::capture_with_handle(
@@ -0,0 +1,169 @@
+//! The error type of this crate.
+
+use crate::cpu::ThreadId;
+
+/// A boxed error kept as the source of [`Error`].
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// The error returned by this crate.
+///
+/// the underlying error is kept as `source` whenever there is one.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// the monitored process has exited, or it never existed.
+    #[error("process {pid} has exited")]
+    ProcessExited { pid: u32 },
+
+    /// the monitored thread has exited, or it never existed.
+    #[error("thread {tid:?} has exited")]
+    ThreadExited { tid: ThreadId },
+
+    /// the operation is denied by OS, e.g. reading other user's process.
+    #[error("permission denied")]
+    PermissionDenied(#[source] BoxedError),
+
+    /// the operation is not supported by this platform or kernel.
+    #[error("{what} is not supported")]
+    Unsupported {
+        what: String,
+        #[source]
+        source: Option<BoxedError>,
+    },
+
+    /// `/proc` is not mounted, e.g. in some sandboxes and containers.
+    #[error("procfs is not mounted")]
+    ProcfsNotMounted(#[source] BoxedError),
+
+    /// an argument passed in is invalid.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// the content read from OS is not in expected format.
+    #[error("cannot parse {0}")]
+    Parse(String),
+
+    /// the OS returns a value that should never happen, e.g. zero clock ticks per second.
+    #[error("unexpected value from OS: {0}")]
+    Unexpected(String),
+
+    #[error(transparent)]
+    Io(std::io::Error),
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[error(transparent)]
+    Procfs(procfs::ProcError),
+}
+
+/// The result returned by this crate.
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Error {
+    /// return an [`Error::Unsupported`] without source.
+    pub fn unsupported(what: impl Into<String>) -> Self {
+        Error::Unsupported {
+            what: what.into(),
+            source: None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::PermissionDenied => Error::PermissionDenied(e.into()),
+            std::io::ErrorKind::Unsupported => Error::Unsupported {
+                what: "the operation".to_string(),
+                source: Some(e.into()),
+            },
+            _ => Error::Io(e),
+        }
+    }
+}
+
+/// for the deprecated functions still returning `std::io::Result`.
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        use std::io::ErrorKind;
+
+        let kind =
+            match &e {
+                Error::Io(e) => e.kind(),
+                Error::ProcessExited { .. }
+                | Error::ThreadExited { .. }
+                | Error::ProcfsNotMounted(_) => ErrorKind::NotFound,
+                Error::PermissionDenied(_) => ErrorKind::PermissionDenied,
+                Error::Unsupported { .. } => ErrorKind::Unsupported,
+                Error::InvalidArgument(_) => ErrorKind::InvalidInput,
+                Error::Parse(_) | Error::Unexpected(_) => ErrorKind::InvalidData,
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Error::Procfs(_) => ErrorKind::Other,
+            };
+
+        match e {
+            Error::Io(e) => e,
+            e => std::io::Error::new(kind, e),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl From<procfs::ProcError> for Error {
+    fn from(e: procfs::ProcError) -> Self {
+        use procfs::ProcError;
+
+        match e {
+            ProcError::PermissionDenied(_) => Error::PermissionDenied(e.into()),
+            ProcError::NotFound(_) if ! crate::utils::process::procfs_mounted() => {
+                Error::ProcfsNotMounted(e.into())
+            }
+            ProcError::Io(e, _) => e.into(),
+            e => Error::Procfs(e),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl From<rustix::io::Errno> for Error {
+    fn from(e: rustix::io::Errno) -> Self {
+        std::io::Error::from(e).into()
+    }
+}
+
+impl From<core::num::TryFromIntError> for Error {
+    fn from(e: core::num::TryFromIntError) -> Self {
+        Error::InvalidArgument(e.to_string())
+    }
+}
+
+impl From<core::num::ParseIntError> for Error {
+    fn from(e: core::num::ParseIntError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod test {
+    use super::*;
+
+    use crate::cpu::{ProcessStat, ThreadStat};
+
+    #[test]
+    fn test_exited() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        let Err(e) = ProcessStat::for_pid(pid) else { panic!("process {} exited", pid) };
+        assert!(matches!(e, Error::ProcessExited { pid: p } if p == pid));
+        assert_eq!(std::io::Error::from(e).kind(), std::io::ErrorKind::NotFound);
+
+        let tid =
+            std::thread::spawn(ThreadId::current)
+            .join()
+            .unwrap();
+        let Err(e) = ThreadStat::try_from(tid) else { panic!("thread {:?} exited", tid) };
+        assert!(matches!(e, Error::ThreadExited { tid: t } if t == tid));
+    }
+}
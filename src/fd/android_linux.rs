@@ -1,25 +1,21 @@
+use crate::utils::process::get_process;
+
 use procfs::process::Process;
 
 #[inline]
 pub fn fd_count_pid<T: Into<u32>>(pid: T)
-    -> anyhow::Result<usize>
+    -> crate::Result<usize>
 {
-    let pid: u32 = pid.into();
-
-    let pid: i32 = pid.try_into()?;
-    Ok(Process::new(pid)?.fd_count()?)
+    Ok( get_process(pid.into())?.fd_count()? )
 }
 
-pub fn fd_count_current() -> anyhow::Result<usize> {
+pub fn fd_count_current() -> crate::Result<usize> {
     Ok(Process::myself()?.fd_count()?)
 }
 
 #[deprecated]
 pub fn fd_count_cur() -> std::io::Result<usize> {
-    match fd_count_current() {
-        Ok(v) => Ok(v),
-        Err(e) => Err( std::io::Error::other(e) ),
-    }
+    Ok( fd_count_current()? )
 }
 
 #[cfg(test)]
@@ -26,11 +26,16 @@ pub struct proc_fdinfo {
     pub proc_fdtype: u32,
 }
 
-pub fn fd_count_cur() -> std::io::Result<usize> {
+pub fn fd_count_current() -> crate::Result<usize> {
     fd_count_pid(std::process::id())
 }
 
-pub fn fd_count_pid(pid: u32) -> std::io::Result<usize> {
+#[deprecated]
+pub fn fd_count_cur() -> std::io::Result<usize> {
+    Ok( fd_count_current()? )
+}
+
+pub fn fd_count_pid(pid: u32) -> crate::Result<usize> {
     let pid = pid as i32;
     let max_fds = unsafe {
         let mut info = MaybeUninit::<proc_taskallinfo>::uninit();
@@ -43,13 +48,12 @@ pub fn fd_count_pid(pid: u32) -> std::io::Result<usize> {
             buffersize,
         );
         if ret <= 0 {
-            return Err(std::io::Error::from_raw_os_error(ret));
+            return Err( std::io::Error::from_raw_os_error(ret).into() );
         }
         if ret < buffersize {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "proc_pidinfo(PROC_PIDTASKALLINFO) too few bytes",
-            ));
+            return Err( crate::Error::Unexpected(
+                "proc_pidinfo(PROC_PIDTASKALLINFO) too few bytes".to_string()
+            ) );
         }
         info.assume_init_ref().pbsd.pbi_nfiles as c_int
     };
@@ -65,7 +69,7 @@ pub fn fd_count_pid(pid: u32) -> std::io::Result<usize> {
         )
     };
     if ret <= 0 {
-        Err(std::io::Error::from_raw_os_error(ret))
+        Err( std::io::Error::from_raw_os_error(ret).into() )
     } else {
         Ok(ret as usize / std::mem::size_of::<proc_fdinfo>())
     }
@@ -81,7 +85,7 @@ mod test {
         {
             let mut buf = vec![];
             const NUM: usize = 100;
-            let init_count = fd_count_cur().unwrap();
+            let init_count = fd_count_current().unwrap();
 
             for i in 0..NUM {
                 let fname = format!("/tmp/fd_count_test_tmpfile{}", i);
@@ -91,13 +95,13 @@ mod test {
                     .open(fname);
                 buf.push(file);
             }
-            let count = fd_count_cur().unwrap();
+            let count = fd_count_current().unwrap();
             assert_eq!(NUM + init_count, count);
         }
 
         // case2: compare the result with lsof.
         {
-            let count_devfd = fd_count_cur().unwrap();
+            let count_devfd = fd_count_current().unwrap();
             let count_lsof = fd_lsof() - 2; // minus pipe fd between parent process and child process.
             assert_eq!(count_lsof, count_devfd);
         }
@@ -2,10 +2,15 @@
 // following links contains a available way, but it's complicated and
 // inefficient. <https://stackoverflow.com/questions/4083608/on-ios-iphone-too-many-open-files-need-to-list-open-files-like-lsof>
 
+pub fn fd_count_current() -> crate::Result<usize> {
+    Err( crate::Error::unsupported("fd count on ios") )
+}
+
+#[deprecated]
 pub fn fd_count_cur() -> std::io::Result<usize> {
-    unimplemented!()
+    Ok( fd_count_current()? )
 }
 
-pub fn fd_count_pid(pid: u32) -> std::io::Result<usize> {
-    unimplemented!()
+pub fn fd_count_pid(_pid: u32) -> crate::Result<usize> {
+    Err( crate::Error::unsupported("fd count on ios") )
 }
@@ -1,14 +1,16 @@
 #[deprecated]
 pub fn fd_count_cur() -> std::io::Result<usize> {
-    fd_count_current()
+    Ok( fd_count_current()? )
 }
 
-pub fn fd_count_current() -> anyhow::Result<usize> {
+pub fn fd_count_current() -> crate::Result<usize> {
     // Remove the opening fd created by `read_dir`
-    std::fs::read_dir("/dev/fd")
+    Ok(
+        std::fs::read_dir("/dev/fd")
         .map(|entries| {
             entries.count().saturating_sub(1)
-        })
+        })?
+    )
 }
 
 #[cfg(test)]
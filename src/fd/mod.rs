@@ -53,7 +53,7 @@ use darwin_private as platform;
 
 /// return the fd count of current process
 #[inline]
-pub fn fd_count_current() -> anyhow::Result<usize> {
+pub fn fd_count_current() -> crate::Result<usize> {
     Ok(
         platform::fd_count_current()
         .map(|count| { count as usize })?
@@ -62,9 +62,6 @@ pub fn fd_count_current() -> anyhow::Result<usize> {
 
 #[deprecated]
 pub fn fd_count_cur() -> std::io::Result<usize> {
-    match fd_count_current() {
-        Ok(v) => Ok(v),
-        Err(e) => Err( std::io::Error::other(e) ),
-    }
+    Ok( fd_count_current()? )
 }
 
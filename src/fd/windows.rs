@@ -1,5 +1,5 @@
 use windows_sys::Win32::{
-    Foundation::{FALSE, HANDLE};
+    Foundation::{FALSE, HANDLE},
     System::Threading::{
         GetCurrentProcess,
         GetProcessHandleCount,
@@ -28,7 +28,7 @@ fn fd_count(handler: HANDLE)
 
 #[inline]
 pub fn fd_count_pid(pid: u32)
-    -> std::io::Result<u32>
+    -> crate::Result<u32>
 {
     // Use PROCESS_QUERY_LIMITED_INFORMATION to acquire less privilege and drop
     // support for Windows Server 2023 and Windows XP:
@@ -46,19 +46,19 @@ pub fn fd_count_pid(pid: u32)
         .map(|x| unsafe { Handle::new(x) });
 
     if let Some(handle) = maybe_handle {
-        fd_count( handle.as_handle() )
+        Ok( fd_count( handle.as_handle() )? )
     } else {
-        return Err( std::io::Error::last_os_error() );
+        Err( std::io::Error::last_os_error().into() )
     }
 }
 
-pub fn fd_count_current() -> std::io::Result<u32> {
-    fd_count(unsafe { GetCurrentProcess() })
+pub fn fd_count_current() -> crate::Result<u32> {
+    Ok( fd_count(unsafe { GetCurrentProcess() })? )
 }
 
 #[deprecated]
 pub fn fd_count_cur() -> std::io::Result<u32> {
-    fd_count_current()
+    Ok( fd_count_current()? )
 }
 
 #[cfg(test)]
@@ -1,34 +1,5 @@
 //! Get io usage for current process.
 
-/*
-use thiserror::Error;
-
-#[derive(Debug, Clone, Error)]
-#[error("IOStatsError({code}):{msg}")]
-pub struct IOStatsError {
-    pub code: i32,
-    pub msg: String,
-}
-
-impl From<std::io::Error> for IOStatsError {
-    fn from(e: std::io::Error) -> Self {
-        Self {
-            code: e.kind() as i32,
-            msg: e.to_string(),
-        }
-    }
-}
-
-impl From<std::num::ParseIntError> for IOStatsError {
-    fn from(e: std::num::ParseIntError) -> Self {
-        Self {
-            code: 0,
-            msg: e.to_string(),
-        }
-    }
-}
-*/
-
 /// A struct represents io status.
 #[derive(Debug, Clone, Default)]
 pub struct IOStats {
@@ -53,7 +24,7 @@ pub struct IOStats {
 ///
 /// in any platforms that is not supported, this function will always returns error.
 pub fn get_process_io_stats()
-    -> anyhow::Result<IOStats>
+    -> crate::Result<IOStats>
 {
     #[cfg(any(
         target_os = "linux",
@@ -65,12 +36,12 @@ pub fn get_process_io_stats()
         return get_process_io_stats_impl();
     }
 
-    anyhow::bail!("cannot get I/O stats: this platform is not supported");
+    Err( crate::Error::unsupported("I/O stats on this platform") )
 }
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 fn get_process_io_stats_impl()
-    -> anyhow::Result<IOStats>
+    -> crate::Result<IOStats>
 {
     use procfs::process::Process;
     let ret = Process::myself()?.io()?;
@@ -86,7 +57,7 @@ fn get_process_io_stats_impl()
 
 #[cfg(target_os = "windows")]
 fn get_process_io_stats_impl()
-    -> anyhow::Result<IOStats>
+    -> crate::Result<IOStats>
 {
     use core::mem::MaybeUninit;
     use windows_sys::Win32::System::Threading::{
@@ -128,7 +99,7 @@ fn get_process_io_stats_impl()
 
 #[cfg(target_os = "macos")]
 fn get_process_io_stats_impl()
-    -> anyhow::Result<IOStats>
+    -> crate::Result<IOStats>
 {
     use libc::{rusage_info_v2, RUSAGE_INFO_V2};
     use core::{mem::MaybeUninit, ffi::c_int};
@@ -18,6 +18,9 @@ pub(crate) mod darwin_bindings {
     );
 }
 
+mod error;
+pub use error::{BoxedError, Error, Result};
+
 pub mod cpu;
 pub use cpu::*;
 
@@ -30,6 +33,15 @@ pub use io::*;
 pub mod fd;
 pub use fd::*;
 
+pub mod thread;
+
+pub mod span;
+
+pub mod task;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod pressure;
+
 mod utils;
 use utils::*;
 
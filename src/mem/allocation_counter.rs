@@ -1,16 +1,85 @@
 use std::{
     alloc::{GlobalAlloc, Layout, System},
-    sync::atomic::{AtomicBool, AtomicIsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicIsize, AtomicU64, Ordering},
 };
 
-pub const MIN_ALIGN: usize = 16; // module `sys_common` is private. https://doc.rust-lang.org/src/std/sys_common/alloc.rs.html#28
+/// the number of power-of-two size classes, enough for any `Layout` size.
+pub const SIZE_CLASSES: usize = 64;
 
 static ALLOCATED: AtomicIsize = AtomicIsize::new(0);
 static ENABLE: AtomicBool = AtomicBool::new(false);
 
+static PEAK: AtomicIsize = AtomicIsize::new(0);
+static TOTAL_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static ALLOCS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCS: AtomicU64 = AtomicU64::new(0);
+static REALLOCS: AtomicU64 = AtomicU64::new(0);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+static SIZE_HISTOGRAM: [AtomicU64; SIZE_CLASSES] = [ZERO; SIZE_CLASSES];
+
+/// the index of the size class of `size`, class `i` holds sizes in `(2^(i-1), 2^i]`.
+fn size_class(size: usize) -> usize {
+    (size.next_power_of_two().trailing_zeros() as usize).min(SIZE_CLASSES - 1)
+}
+
+/// A snapshot of the counters of [`CountingAllocator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// the bytes in use.
+    pub allocated: isize,
+
+    /// the peak of `allocated` since last reset.
+    pub peak: isize,
+
+    /// the bytes ever allocated, including the growth of reallocations.
+    pub total_allocated: u64,
+
+    /// the number of allocations, `alloc_zeroed` included.
+    pub allocs: u64,
+
+    /// the number of deallocations.
+    pub deallocs: u64,
+
+    /// the number of reallocations.
+    pub reallocs: u64,
+
+    /// the number of allocations and reallocations in each size class,
+    /// `size_histogram[i]` counts the sizes in `(2^(i-1), 2^i]`.
+    pub size_histogram: [u64; SIZE_CLASSES],
+}
+
+impl Default for AllocationStats {
+    fn default() -> Self {
+        AllocationStats {
+            allocated: 0,
+            peak: 0,
+            total_allocated: 0,
+            allocs: 0,
+            deallocs: 0,
+            reallocs: 0,
+            size_histogram: [0; SIZE_CLASSES],
+        }
+    }
+}
+
+impl AllocationStats {
+    /// return the upper bound in bytes and the count of non-empty size classes, from small to large.
+    pub fn size_classes(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.size_histogram.iter()
+            .enumerate()
+            .filter(|(_, count)| { **count > 0 })
+            .map(|(i, count)| { (1usize << i, *count) })
+    }
+}
+
 /// An allocator tracks inuse allocated bytes.
 ///
 /// The counter is disable by default. Please enable it by `CountingAllocator::enable()` then call `CountingAllocator::get_allocated()` will return the bytes inused.
+///
+/// Besides the bytes in use, it counts allocations, deallocations and reallocations, the peak
+/// and total of allocated bytes and a size-class histogram, see `CountingAllocator::stats()`.
 pub struct CountingAllocator;
 
 impl CountingAllocator {
@@ -19,14 +88,57 @@ impl CountingAllocator {
         ALLOCATED.load(Ordering::SeqCst)
     }
 
+    /// Get the peak of inuse bytes since last reset.
+    pub fn get_peak() -> isize {
+        PEAK.load(Ordering::SeqCst)
+    }
+
+    /// Get the bytes ever allocated since last reset.
+    pub fn get_total_allocated() -> u64 {
+        TOTAL_ALLOCATED.load(Ordering::SeqCst)
+    }
+
+    /// Get a snapshot of all counters.
+    ///
+    /// the counters are read one by one, so they may be slightly inconsistent with each other.
+    pub fn stats() -> AllocationStats {
+        let mut size_histogram = [0; SIZE_CLASSES];
+        for (count, class) in size_histogram.iter_mut().zip(SIZE_HISTOGRAM.iter()) {
+            *count = class.load(Ordering::SeqCst);
+        }
+
+        AllocationStats {
+            allocated: ALLOCATED.load(Ordering::SeqCst),
+            peak: PEAK.load(Ordering::SeqCst),
+            total_allocated: TOTAL_ALLOCATED.load(Ordering::SeqCst),
+            allocs: ALLOCS.load(Ordering::SeqCst),
+            deallocs: DEALLOCS.load(Ordering::SeqCst),
+            reallocs: REALLOCS.load(Ordering::SeqCst),
+            size_histogram,
+        }
+    }
+
     /// Check whether the counter is enable.
     pub fn is_enable() -> bool {
         ENABLE.load(Ordering::SeqCst)
     }
 
-    /// Reset the counter.
+    /// Reset all counters.
     pub fn reset() {
-        ALLOCATED.store(0, Ordering::SeqCst)
+        ALLOCATED.store(0, Ordering::SeqCst);
+        PEAK.store(0, Ordering::SeqCst);
+        TOTAL_ALLOCATED.store(0, Ordering::SeqCst);
+        ALLOCS.store(0, Ordering::SeqCst);
+        DEALLOCS.store(0, Ordering::SeqCst);
+        REALLOCS.store(0, Ordering::SeqCst);
+        for class in SIZE_HISTOGRAM.iter() {
+            class.store(0, Ordering::SeqCst);
+        }
+    }
+
+    /// Reset the peak to the current inuse bytes.
+    pub fn reset_peak() {
+        PEAK.store(ALLOCATED.load(Ordering::SeqCst), Ordering::SeqCst)
     }
 
     /// Enable the counter.
@@ -38,6 +150,15 @@ impl CountingAllocator {
     pub fn disable() {
         ENABLE.store(false, Ordering::SeqCst)
     }
+
+    #[inline]
+    fn record_alloc(size: usize) {
+        let allocated = ALLOCATED.fetch_add(size as isize, Ordering::SeqCst) + size as isize;
+        PEAK.fetch_max(allocated, Ordering::SeqCst);
+        TOTAL_ALLOCATED.fetch_add(size as u64, Ordering::SeqCst);
+        ALLOCS.fetch_add(1, Ordering::SeqCst);
+        SIZE_HISTOGRAM[size_class(size)].fetch_add(1, Ordering::SeqCst);
+    }
 }
 
 unsafe impl GlobalAlloc for CountingAllocator {
@@ -45,7 +166,7 @@ unsafe impl GlobalAlloc for CountingAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let ret = System.alloc(layout);
         if !ret.is_null() && Self::is_enable() {
-            ALLOCATED.fetch_add(layout.size() as isize, Ordering::SeqCst);
+            Self::record_alloc(layout.size());
         }
         ret
     }
@@ -55,18 +176,23 @@ unsafe impl GlobalAlloc for CountingAllocator {
         System.dealloc(ptr, layout);
         if Self::is_enable() {
             ALLOCATED.fetch_sub(layout.size() as isize, Ordering::SeqCst);
+            DEALLOCS.fetch_add(1, Ordering::SeqCst);
         }
     }
 
     #[inline]
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
         let ret: *mut u8 = System.realloc(ptr, layout, new_size);
-        if !ret.is_null()
-            && Self::is_enable()
-            && layout.align() <= MIN_ALIGN
-            && layout.align() <= new_size
-        {
-            ALLOCATED.fetch_add(new_size as isize - layout.size() as isize, Ordering::SeqCst);
+        // `System` keeps the alignment of `layout` for any `new_size`, so every success is counted.
+        if !ret.is_null() && Self::is_enable() {
+            let growth = new_size as isize - layout.size() as isize;
+            let allocated = ALLOCATED.fetch_add(growth, Ordering::SeqCst) + growth;
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+            if growth > 0 {
+                TOTAL_ALLOCATED.fetch_add(growth as u64, Ordering::SeqCst);
+            }
+            REALLOCS.fetch_add(1, Ordering::SeqCst);
+            SIZE_HISTOGRAM[size_class(new_size)].fetch_add(1, Ordering::SeqCst);
         }
         ret
     }
@@ -75,11 +201,62 @@ unsafe impl GlobalAlloc for CountingAllocator {
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
         let ret = System.alloc_zeroed(layout);
         if !ret.is_null() && Self::is_enable() {
-            ALLOCATED.fetch_add(layout.size() as isize, Ordering::SeqCst);
+            Self::record_alloc(layout.size());
         }
         ret
     }
 }
+
+// the counters are process-wide, so the exact counts only hold if the allocator
+// is not installed as the global allocator by the `allocation_counter` feature.
+#[cfg(all(test, not(feature = "allocation_counter")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_counting_allocator() {
+        assert_eq!(size_class(0), 0);
+        assert_eq!(size_class(1), 0);
+        assert_eq!(size_class(2), 1);
+        assert_eq!(size_class(3), 2);
+        assert_eq!(size_class(4096), 12);
+        assert_eq!(size_class(4097), 13);
+
+        // only the calls below are counted.
+        CountingAllocator::enable();
+        CountingAllocator::reset();
+
+        unsafe {
+            let layout = Layout::from_size_align(100, 8).unwrap();
+            let ptr = CountingAllocator.alloc(layout);
+            let ptr = CountingAllocator.realloc(ptr, layout, 1000);
+            let layout = Layout::from_size_align(1000, 8).unwrap();
+            CountingAllocator.dealloc(ptr, layout);
+
+            // over-aligned reallocations are counted as well.
+            let layout = Layout::from_size_align(8, 64).unwrap();
+            let ptr = CountingAllocator.alloc(layout);
+            let ptr = CountingAllocator.realloc(ptr, layout, 16);
+            let layout = Layout::from_size_align(16, 64).unwrap();
+            CountingAllocator.dealloc(ptr, layout);
+        }
+
+        let stats = CountingAllocator::stats();
+        assert_eq!(stats.allocated, 0);
+        assert_eq!(stats.peak, 1000);
+        assert_eq!(stats.total_allocated, 1016);
+        assert_eq!((stats.allocs, stats.deallocs, stats.reallocs), (2, 2, 2));
+        assert_eq!(
+            stats.size_classes().collect::<Vec<_>>(),
+            vec![(8, 1), (16, 1), (128, 1), (1024, 1)]
+        );
+
+        CountingAllocator::reset_peak();
+        assert_eq!(CountingAllocator::get_peak(), 0);
+        CountingAllocator::disable();
+    }
+}
+
 #[cfg(feature = "allocation_counter")]
 #[global_allocator]
-static _COUNTER: perf_monitor::mem::CountingAllocator = perf_monitor::mem::CountingAllocator;
+static _COUNTER: crate::mem::CountingAllocator = crate::mem::CountingAllocator;
@@ -0,0 +1,140 @@
+//! Proportional and unique memory of a process, Linux & Android only.
+//!
+//! RSS counts a page shared by N processes N times, e.g. the shared libraries and the
+//! copy-on-write pages of prefork workers, so the sum of RSS overstates the memory used.
+//!
+//! | metric | meaning |
+//! | -- | -- |
+//! | PSS | RSS with each shared page divided by the number of processes sharing it |
+//! | USS | the pages private to the process, i.e. freed once the process exits |
+//!
+//! They are read from `/proc/{pid}/smaps_rollup` (linux 4.14+), or summed up from
+//! `/proc/{pid}/smaps` on older kernels.
+//!
+//! ```
+//! # use perfmon::mem::ProcessMemoryDetails;
+//! let details = ProcessMemoryDetails::current().unwrap();
+//! println!("rss: {}, pss: {}, uss: {}", details.rss, details.pss, details.uss());
+//! ```
+
+use crate::utils::process::get_process;
+use crate::Error;
+
+use procfs::process::MemoryMaps;
+use procfs::ProcError;
+
+/// The memory of a process split by how it is shared, all in bytes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ProcessMemoryDetails {
+    /// the resident set size.
+    pub rss: u64,
+
+    /// the proportional set size.
+    pub pss: u64,
+
+    /// resident pages shared with other processes and not modified.
+    pub shared_clean: u64,
+
+    /// resident pages shared with other processes and modified.
+    pub shared_dirty: u64,
+
+    /// resident pages private to the process and not modified.
+    pub private_clean: u64,
+
+    /// resident pages private to the process and modified.
+    pub private_dirty: u64,
+
+    /// resident pages not backed by a file.
+    pub anonymous: u64,
+
+    /// the anonymous memory swapped out.
+    pub swap: u64,
+
+    /// the proportional share of `swap`, zero before linux 4.3.
+    pub swap_pss: u64,
+}
+
+impl ProcessMemoryDetails {
+    /// return the memory details of current process.
+    pub fn current() -> crate::Result<Self> {
+        Self::for_pid(std::process::id())
+    }
+
+    /// return the memory details of the process specified by `pid`.
+    ///
+    /// reading other user's process requires ptrace permission, or it fails with
+    /// [`Error::PermissionDenied`].
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        let process = get_process(pid)?;
+
+        let maps =
+            match process.smaps_rollup() {
+                Ok(rollup) => rollup.memory_map_rollup,
+                // smaps_rollup is missing before linux 4.14.
+                Err(ProcError::NotFound(_)) => {
+                    match process.smaps() {
+                        Ok(maps) => maps,
+                        Err(ProcError::NotFound(_)) => {
+                            return Err( Error::ProcessExited { pid } );
+                        }
+                        Err(e) => {
+                            return Err(e.into());
+                        }
+                    }
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
+            };
+
+        Ok( Self::from_maps(&maps) )
+    }
+
+    /// sum up the extension of all mappings, a rollup has only one.
+    fn from_maps(maps: &MemoryMaps) -> Self {
+        let get = |name: &str| {
+            maps.iter()
+                .filter_map(|map| { map.extension.map.get(name) })
+                .sum::<u64>()
+        };
+
+        ProcessMemoryDetails {
+            rss: get("Rss"),
+            pss: get("Pss"),
+            shared_clean: get("Shared_Clean"),
+            shared_dirty: get("Shared_Dirty"),
+            private_clean: get("Private_Clean"),
+            private_dirty: get("Private_Dirty"),
+            anonymous: get("Anonymous"),
+            swap: get("Swap"),
+            swap_pss: get("SwapPss"),
+        }
+    }
+
+    /// the unique set size, that is the memory freed once the process exits.
+    pub fn uss(&self) -> u64 {
+        self.private_clean + self.private_dirty
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_process_memory_details() {
+        let details = ProcessMemoryDetails::current().unwrap();
+        assert!(details.rss > 0);
+        assert!(details.pss <= details.rss);
+        assert!(details.uss() <= details.pss);
+        assert_eq!(
+            details.rss,
+            details.shared_clean + details.shared_dirty + details.uss()
+        );
+
+        let smaps = procfs::process::Process::myself().unwrap().smaps().unwrap();
+        let summed = ProcessMemoryDetails::from_maps(&smaps);
+        assert!(summed.rss > 0);
+        assert!(summed.uss() <= summed.pss);
+    }
+}
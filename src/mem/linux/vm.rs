@@ -0,0 +1,263 @@
+//! An iterator over the virtual memory regions in `/proc/{pid}/smaps`.
+//!
+//! ```
+//! # use perfmon::mem::linux::vm::{VMRegionIter, VMRegionKind};
+//! # use std::collections::HashMap;
+//! let mut resident = HashMap::<VMRegionKind, u64>::new();
+//! for region in VMRegionIter::current().unwrap() {
+//!     *resident.entry(region.kind()).or_default() += region.resident_bytes();
+//! }
+//! println!("{:?}", resident);
+//! ```
+
+use crate::utils::process::get_process;
+use crate::Error;
+
+use procfs::process::{MMPermissions, MMapPath, MemoryMap};
+use procfs::ProcError;
+
+use std::path::Path;
+
+/// The kind of a region, classified by its pathname and permissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VMRegionKind {
+    /// the heap grown by `brk`.
+    Heap,
+    /// the stack of main thread.
+    Stack,
+    /// the stack of other threads, recognized as the anonymous region containing the stack
+    /// pointer of a thread.
+    ///
+    /// the stack pointer is read from `/proc/{pid}/task/{tid}/syscall`, which is only available
+    /// for threads blocked at that moment, so the stack of a thread running on another cpu
+    /// is classified as [`VMRegionKind::Anonymous`].
+    ThreadStack,
+    /// anonymous mmap, e.g. allocations of malloc arenas or large allocations.
+    Anonymous,
+    /// a file mapped into memory, including the executable itself.
+    File,
+    /// a shared library (`*.so`) mapped by the dynamic linker.
+    SharedLibrary,
+    /// the vdso, vvar and vsyscall regions provided by kernel.
+    Vdso,
+    /// an inaccessible region, e.g. stack guard pages or reserved address space.
+    Guard,
+    /// other pseudo-paths, e.g. SysV shared memory.
+    Other,
+}
+
+fn is_shared_library(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| { n.to_str() }) else {
+        return false;
+    };
+    // `libc.so.6`, `libfoo.so` or `libfoo.so (deleted)`
+    name.split(' ').next()
+        .and_then(|name| { name.find(".so").map(|i| { &name[i + 3..] }) })
+        .map(|rest| { rest.is_empty() || rest.starts_with('.') })
+        .unwrap_or(false)
+}
+
+/// A region of virtual memory with its usage, all sizes in bytes.
+#[derive(Debug, Clone)]
+pub struct VMRegion {
+    map: MemoryMap,
+    kind: VMRegionKind,
+}
+
+impl VMRegion {
+    fn get(&self, name: &str) -> u64 {
+        self.map.extension.map.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn kind(&self) -> VMRegionKind {
+        self.kind
+    }
+
+    /// the start address of this region.
+    pub fn start(&self) -> u64 {
+        self.map.address.0
+    }
+
+    /// the end address of this region, exclusive.
+    pub fn end(&self) -> u64 {
+        self.map.address.1
+    }
+
+    pub fn size(&self) -> u64 {
+        self.end() - self.start()
+    }
+
+    /// the file backing this region, `None` for anonymous and pseudo regions.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.map.pathname {
+            MMapPath::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.map.perms.contains(MMPermissions::WRITE)
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.map.perms.contains(MMPermissions::EXECUTE)
+    }
+
+    pub fn resident_bytes(&self) -> u64 {
+        self.get("Rss")
+    }
+
+    pub fn dirty_bytes(&self) -> u64 {
+        self.get("Shared_Dirty") + self.get("Private_Dirty")
+    }
+
+    pub fn swapped_bytes(&self) -> u64 {
+        self.get("Swap")
+    }
+}
+
+/// return the stack pointers of the threads of a process.
+///
+/// `/proc/{pid}/task/{tid}/syscall` ends with the stack pointer and program counter,
+/// or it is `running` if the thread is running.
+fn stack_pointers(pid: u32) -> crate::Result<Vec<u64>> {
+    let mut sps = Vec::new();
+    for task in get_process(pid)?.tasks()? {
+        let Ok(task) = task else { continue };
+        let path = format!("/proc/{}/task/{}/syscall", pid, task.tid);
+        let Ok(content) = std::fs::read_to_string(path) else { continue };
+
+        let fields: Vec<&str> = content.split_whitespace().collect();
+        let Some(sp) = fields.len().checked_sub(2).map(|i| { fields[i] }) else { continue };
+        let Some(sp) = sp.strip_prefix("0x") else { continue };
+        if let Ok(sp) = u64::from_str_radix(sp, 16) {
+            sps.push(sp);
+        }
+    }
+
+    // current thread is always running when it reads its own syscall file.
+    if pid == std::process::id() {
+        let local = 0u8;
+        sps.push( std::hint::black_box(&local) as *const u8 as u64 );
+    }
+    Ok(sps)
+}
+
+/// An iterator over the regions of a process, in the order of address.
+pub struct VMRegionIter {
+    maps: std::vec::IntoIter<MemoryMap>,
+
+    /// the stack pointers of threads, see [`VMRegionKind::ThreadStack`].
+    stack_pointers: Vec<u64>,
+}
+
+impl VMRegionIter {
+    /// return the regions of current process.
+    pub fn current() -> crate::Result<Self> {
+        Self::for_pid(std::process::id())
+    }
+
+    /// return the regions of the process specified by `pid`, read at once.
+    ///
+    /// reading other user's process requires ptrace permission.
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        let maps =
+            match get_process(pid)?.smaps() {
+                Ok(maps) => maps,
+                Err(ProcError::NotFound(_)) => {
+                    return Err( Error::ProcessExited { pid } );
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
+            };
+
+        Ok(VMRegionIter {
+            maps: maps.into_iter(),
+            stack_pointers: stack_pointers(pid)?,
+        })
+    }
+
+    fn classify(&self, map: &MemoryMap) -> VMRegionKind {
+        let accessible =
+            map.perms.intersects(MMPermissions::READ | MMPermissions::WRITE | MMPermissions::EXECUTE);
+        let (start, end) = map.address;
+        let has_stack_pointer =
+            self.stack_pointers.iter()
+            .any(|sp| { start <= *sp && *sp < end });
+
+        match &map.pathname {
+            MMapPath::Heap => VMRegionKind::Heap,
+            MMapPath::Stack => VMRegionKind::Stack,
+            MMapPath::TStack(_) => VMRegionKind::ThreadStack,
+            MMapPath::Vdso | MMapPath::Vvar | MMapPath::Vsyscall => VMRegionKind::Vdso,
+            MMapPath::Path(path) if is_shared_library(path) => VMRegionKind::SharedLibrary,
+            MMapPath::Path(_) => VMRegionKind::File,
+            MMapPath::Anonymous if ! accessible => VMRegionKind::Guard,
+            MMapPath::Anonymous if has_stack_pointer => VMRegionKind::ThreadStack,
+            MMapPath::Anonymous => VMRegionKind::Anonymous,
+            // `[anon:name]` named by `prctl(PR_SET_VMA_ANON_NAME)`.
+            MMapPath::Other(name) if name.starts_with("[anon:") => VMRegionKind::Anonymous,
+            _ => VMRegionKind::Other,
+        }
+    }
+}
+
+impl Iterator for VMRegionIter {
+    type Item = VMRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.maps.next()?;
+        let kind = self.classify(&map);
+        Some( VMRegion { map, kind } )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vm_region_iter() {
+        assert!(is_shared_library(Path::new("/usr/lib/x86_64-linux-gnu/libc.so.6")));
+        assert!(is_shared_library(Path::new("/tmp/libfoo.so (deleted)")));
+        assert!(! is_shared_library(Path::new("/usr/bin/some.source")));
+
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel::<u64>();
+        let handle =
+            std::thread::spawn(move || {
+                let local = 0u8;
+                ready_tx.send(std::hint::black_box(&local) as *const u8 as u64).unwrap();
+                rx.recv().ok();
+            });
+        let blocked = ready_rx.recv().unwrap();
+        // wait for the thread to block in kernel.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        let regions: Vec<_> = VMRegionIter::current().unwrap().collect();
+        let has = |kind| { regions.iter().any(|r| { r.kind() == kind }) };
+        assert!(has(VMRegionKind::Stack));
+        assert!(has(VMRegionKind::File));
+        assert!(has(VMRegionKind::Anonymous) || has(VMRegionKind::Heap));
+
+        let kind_of = |addr: u64| {
+            regions.iter()
+                .find(|r| { r.start() <= addr && addr < r.end() })
+                .map(|r| { r.kind() })
+        };
+        // the test runs on a thread other than main thread.
+        let local = 0u8;
+        let me = std::hint::black_box(&local) as *const u8 as u64;
+        assert_eq!(kind_of(me), Some(VMRegionKind::ThreadStack));
+        assert_eq!(kind_of(blocked), Some(VMRegionKind::ThreadStack));
+
+        for region in &regions {
+            assert!(region.resident_bytes() <= region.size());
+            assert!(region.dirty_bytes() <= region.resident_bytes());
+        }
+
+        drop(tx);
+        handle.join().unwrap();
+    }
+}
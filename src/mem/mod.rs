@@ -1,8 +1,11 @@
 //! This sub-mod provides some facilities about memory performance profiling.
 //! # Memory usage of current process
-//! There's a platform-related function called `get_process_memory_info` available on MacOS and Windows.
+//! There's a platform-related function called `get_process_memory_info` available on Linux, Android, MacOS and Windows.
+//! On Linux and Android, `ProcessMemoryDetails` tells PSS and USS apart from RSS, for any process,
+//! and `PageFaultStat` monitors minor and major page faults of a thread or process.
 //! # Memory usage of ALL Rust allocations
 //! We provide a `CountingAllocator` that wraps the system allocator but tracks the bytes used by rust allocations.
+//! It also counts allocations, the peak and total bytes and a size-class histogram, see `CountingAllocator::stats()`.
 //! This crate DOES NOT replace the global allocator by default. You need to make it as a `global_allocator` or enable the `allocation_counter` feature.
 //! ```ignore
 //! #[global_allocator]
@@ -11,11 +14,24 @@
 
 mod allocation_counter;
 
-pub use allocation_counter::CountingAllocator;
+pub use allocation_counter::{AllocationStats, CountingAllocator, SIZE_CLASSES};
 
 mod process_memory_info;
 pub use process_memory_info::{get_process_memory_info, ProcessMemoryInfo};
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod details;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use details::ProcessMemoryDetails;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod page_fault;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use page_fault::{PageFaultDelta, PageFaultStat, PageFaults};
+
 #[cfg(target_os = "macos")]
 #[cfg_attr(doc, doc(cfg(macos)))]
 pub mod apple;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod linux;
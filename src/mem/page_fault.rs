@@ -0,0 +1,185 @@
+//! Page faults of a thread or process, Linux & Android only.
+//!
+//! A minor fault is resolved without io, e.g. the first touch of an allocated page,
+//! while a major fault waits for the page read from disk or swap, which is
+//! usually the cause of latency spikes after memory is swapped out.
+//!
+//! | target | source |
+//! | -- | -- |
+//! | thread | `minflt` & `majflt` of `/proc/{pid}/task/{tid}/stat` |
+//! | process | `minflt` & `majflt` of `/proc/{pid}/stat`, including exited threads |
+//!
+//! ```
+//! # use perfmon::mem::PageFaultStat;
+//! let stat = PageFaultStat::current_process().unwrap();
+//! let _buf = vec![1u8; 1 << 20];
+//! let delta = stat.sample().unwrap();
+//! println!("minor faults: {:.2}/s, major faults: {:.2}/s", delta.minor_per_sec(), delta.major_per_sec());
+//! ```
+
+use crate::cpu::{get_process_stat, get_thread_stat, ThreadId};
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use std::time::Instant;
+
+/// cumulative page fault counters.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PageFaults {
+    /// the number of faults which have not required loading a page from disk.
+    pub minor: u64,
+
+    /// the number of faults which have required loading a page from disk.
+    pub major: u64,
+}
+
+impl PageFaults {
+    pub fn saturating_sub(&self, rhs: &PageFaults) -> PageFaults {
+        PageFaults {
+            minor: self.minor.saturating_sub(rhs.minor),
+            major: self.major.saturating_sub(rhs.major),
+        }
+    }
+}
+
+/// page faults between two samples.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PageFaultDelta {
+    pub faults: PageFaults,
+
+    /// the real time elapsed between two samples.
+    pub elapsed: Duration,
+}
+
+impl PageFaultDelta {
+    fn per_sec(&self, count: u64) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        count as f64 / elapsed
+    }
+
+    pub fn minor_per_sec(&self) -> f64 {
+        self.per_sec(self.faults.minor)
+    }
+
+    pub fn major_per_sec(&self) -> f64 {
+        self.per_sec(self.faults.major)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    /// a thread in current process.
+    Thread(ThreadId),
+    Process(u32),
+}
+
+impl Target {
+    fn read_faults(&self) -> crate::Result<PageFaults> {
+        let stat =
+            match *self {
+                Target::Thread(tid) => get_thread_stat(tid)?,
+                Target::Process(pid) => get_process_stat(pid)?,
+            };
+
+        Ok(PageFaults {
+            minor: stat.minflt,
+            major: stat.majflt,
+        })
+    }
+}
+
+/// A struct to monitor page faults of a thread or process.
+pub struct PageFaultStat {
+    target: Target,
+    last_stat: Cell<(PageFaults, Instant)>,
+}
+
+impl PageFaultStat {
+    fn new(target: Target) -> crate::Result<Self> {
+        let faults = target.read_faults()?;
+        let now = Instant::now();
+        Ok(PageFaultStat {
+            target,
+            last_stat: Cell::new( (faults, now) ),
+        })
+    }
+
+    /// return a monitor of current thread.
+    pub fn current_thread() -> crate::Result<Self> {
+        Self::for_thread(ThreadId::current())
+    }
+
+    /// return a monitor of specified thread in current process.
+    pub fn for_thread(tid: ThreadId) -> crate::Result<Self> {
+        Self::new( Target::Thread(tid) )
+    }
+
+    /// return a monitor of current process.
+    pub fn current_process() -> crate::Result<Self> {
+        Self::for_pid(std::process::id())
+    }
+
+    /// return a monitor of the process specified by `pid`.
+    pub fn for_pid(pid: u32) -> crate::Result<Self> {
+        Self::new( Target::Process(pid) )
+    }
+
+    /// return the cumulative counters, it does not affect the baseline of `sample`.
+    pub fn faults(&self) -> crate::Result<PageFaults> {
+        self.target.read_faults()
+    }
+
+    /// return the page faults from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn sample(&self) -> crate::Result<PageFaultDelta> {
+        let faults = self.target.read_faults()?;
+        let now = Instant::now();
+
+        let (old_faults, old_now) =
+            self.last_stat.replace(
+                (faults, now)
+            );
+
+        Ok(PageFaultDelta {
+            faults: faults.saturating_sub(&old_faults),
+            elapsed: now.saturating_duration_since(old_now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_page_fault_stat() {
+        let thread = PageFaultStat::current_thread().unwrap();
+        let process = PageFaultStat::current_process().unwrap();
+
+        // touch every page of a fresh mapping.
+        let buf = vec![1u8; 16 << 20];
+        std::hint::black_box(&buf);
+
+        let delta = thread.sample().unwrap();
+        assert!(delta.faults.minor > 0);
+        assert!(delta.minor_per_sec() > 0.0);
+
+        assert!(process.sample().unwrap().faults.minor > 0);
+
+        let me = thread.faults().unwrap();
+        let total = process.faults().unwrap();
+        assert!(total.minor >= me.minor);
+
+        // a killed child is a zombie until it is reaped, which is treated as exited.
+        let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        let stat = PageFaultStat::for_pid(child.id()).unwrap();
+        child.kill().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(matches!(stat.sample(), Err(crate::Error::ProcessExited { .. })));
+        child.wait().unwrap();
+    }
+}
@@ -1,5 +1,3 @@
-use std::io::{Error, Result};
-
 /// Process Memory Info returned by `get_process_memory_info`
 #[derive(Debug, Clone, Default)]
 pub struct ProcessMemoryInfo {
@@ -10,6 +8,9 @@ pub struct ProcessMemoryInfo {
     /// column of taskmgr.exe.
     pub resident_set_size: u64,
 
+    /// the peak of `resident_set_size`.
+    ///
+    /// On Linux it is `VmHWM` of `/proc/self/status`.
     pub resident_set_size_peak: Option<u64>,
 
     /// this is the total amount of virtual memory used by the process.
@@ -19,6 +20,11 @@ pub struct ProcessMemoryInfo {
     /// Usage" "VM Size" column of taskmgr.exe.
     pub virtual_memory_size: u64,
 
+    /// the peak of `virtual_memory_size`.
+    ///
+    /// On Linux it is `VmPeak` of `/proc/self/status`, on Windows it is the peak of pagefile.
+    pub virtual_memory_size_peak: Option<u64>,
+
     ///  This is the sum of:
     ///
     ///    + (internal - alternate_accounting)
@@ -37,11 +43,26 @@ pub struct ProcessMemoryInfo {
     pub phys_footprint: Option<u64>,
 
     pub compressed: Option<u64>,
+
+    /// resident memory backed by a file or shared with other processes, Linux only.
+    pub shared: Option<u64>,
+
+    /// the size of code mapped from executable, Linux only.
+    pub text: Option<u64>,
+
+    /// the size of data and stack, Linux only.
+    pub data: Option<u64>,
+
+    /// the size of memory locked by `mlock`, Linux only.
+    pub locked: Option<u64>,
+
+    /// the size of page tables, Linux only.
+    pub page_table: Option<u64>,
 }
 
 #[cfg(target_os = "windows")]
 fn get_process_memory_info_impl()
-    -> anyhow::Result<ProcessMemoryInfo>
+    -> crate::Result<ProcessMemoryInfo>
 {
     use core::mem::MaybeUninit;
     use windows_sys::Win32::System::{
@@ -71,7 +92,7 @@ fn get_process_memory_info_impl()
     };
 
     if ret == 0 {
-        return Err(Error::last_os_error());
+        return Err( std::io::Error::last_os_error().into() );
     }
 
     let pmc =
@@ -87,31 +108,48 @@ fn get_process_memory_info_impl()
         virtual_memory_size:
             pmc.PagefileUsage as u64,
 
-        phys_footprint: None,
-        compressed: None,
+        virtual_memory_size_peak:
+            Some(pmc.PeakPagefileUsage as u64),
+
+        ..Default::default()
     })
 }
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 fn get_process_memory_info_impl()
-    -> anyhow::Result<ProcessMemoryInfo>
+    -> crate::Result<ProcessMemoryInfo>
 {
     // https://www.kernel.org/doc/Documentation/filesystems/proc.txt
 
     use procfs::process::Process;
-    let statm = Process::myself()?.statm()?;
+
+    let process = Process::myself()?;
+    let statm = process.statm()?;
+    let status = process.status()?;
+
+    // statm is in pages, while status is in kB.
+    let page_size = procfs::page_size();
+    let kb = |v: Option<u64>| { v.map(|v| { v * 1024 }) };
+
     Ok(ProcessMemoryInfo {
-        virtual_memory_size: statm.size,
-        resident_set_size: statm.resident,
+        virtual_memory_size: statm.size * page_size,
+        resident_set_size: statm.resident * page_size,
+
+        resident_set_size_peak: kb(status.vmhwm),
+        virtual_memory_size_peak: kb(status.vmpeak),
+
+        shared: Some(statm.shared * page_size),
+        text: Some(statm.text * page_size),
+        data: Some(statm.data * page_size),
+        locked: kb(status.vmlck),
+        page_table: kb(status.vmpte),
 
-        resident_set_size_peak: None,
-        phys_footprint: None,
-        compressed: None,
+        ..Default::default()
     })
 }
 
 #[cfg(target_vendor="apple")]
-fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
+fn get_process_memory_info_impl() -> crate::Result<ProcessMemoryInfo> {
     //use crate::bindings::task_vm_info;
 
     use core::mem::MaybeUninit;
@@ -153,10 +191,10 @@ fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
 
     if kern_ret != KERN_SUCCESS {
         // see https://docs.rs/mach-sys/0.5.1/mach-sys/kern_return/index.html for more details
-        anyhow::bail!(
+        return Err( crate::Error::Io(std::io::Error::other(format!(
             "DARWIN_KERN_RET_CODE: {}",
             kern_ret
-        );
+        ))) );
     }
 
     let task_vm_info =
@@ -172,9 +210,32 @@ fn get_process_memory_info_impl() -> Result<ProcessMemoryInfo> {
             Some(task_vm_info.phys_footprint),
         compressed:
             Some(task_vm_info.compressed),
+
+        ..Default::default()
     })
 }
 
-pub fn get_process_memory_info() -> anyhow::Result<ProcessMemoryInfo> {
+pub fn get_process_memory_info() -> crate::Result<ProcessMemoryInfo> {
     get_process_memory_info_impl()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_process_memory_info() {
+        let info = get_process_memory_info().unwrap();
+        assert!(info.resident_set_size > 0);
+        assert!(info.virtual_memory_size >= info.resident_set_size);
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            // in bytes rather than pages.
+            assert!(info.resident_set_size >= 1024 * 1024);
+            assert!(info.resident_set_size_peak.unwrap() >= info.resident_set_size);
+            assert!(info.virtual_memory_size_peak.unwrap() >= info.virtual_memory_size);
+            assert!(info.text.unwrap() > 0);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Pressure Stall Information (PSI) and load average, Linux & Android only.
+//!
+//! Unlike cpu usage, pressure tells how long tasks are stalled waiting for
+//! cpu, memory or io, that is the time lost rather than the time used.
+//!
+//! | scope | source |
+//! | -- | -- |
+//! | system | `/proc/pressure/{cpu,memory,io}` |
+//! | cgroup v2 | `{cgroup}/{cpu,memory,io}.pressure` |
+//! | load average | `/proc/loadavg` |
+//!
+//! PSI requires linux 4.20+ built with `CONFIG_PSI`, and it can be disabled by
+//! `psi=0` in kernel command line. Use [`is_supported`] to check it.
+//! See [psi](https://docs.kernel.org/accounting/psi.html) for details.
+//!
+//! ```
+//! # use perfmon::pressure::{is_supported, PressureStat, Resource};
+//! if is_supported() {
+//!     let stat = PressureStat::system(Resource::Cpu).unwrap();
+//!     std::thread::sleep(std::time::Duration::from_millis(10));
+//!     let delta = stat.sample().unwrap();
+//!     println!("stalled on cpu: {:.2}%", delta.some_ratio() * 100f64);
+//! }
+//! ```
+
+use crate::utils::cgroup::Cgroup;
+use crate::Error;
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// The resource whose pressure is measured.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl Resource {
+    fn name(&self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Io => "io",
+        }
+    }
+}
+
+/// A line of pressure file.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PressureRecord {
+    /// the percentage of stalled time in the last 10 seconds.
+    pub avg10: f64,
+
+    /// the percentage of stalled time in the last 60 seconds.
+    pub avg60: f64,
+
+    /// the percentage of stalled time in the last 300 seconds.
+    pub avg300: f64,
+
+    /// the cumulative stalled time.
+    pub total: Duration,
+}
+
+/// The pressure of a resource.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Pressure {
+    /// some tasks are stalled.
+    pub some: PressureRecord,
+
+    /// all non-idle tasks are stalled simultaneously.
+    ///
+    /// `None` for system-wide cpu before linux 5.13.
+    pub full: Option<PressureRecord>,
+}
+
+fn parse_record(fields: &str) -> Option<PressureRecord> {
+    let mut record = PressureRecord::default();
+    for field in fields.split_whitespace() {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "avg10" => record.avg10 = value.parse().ok()?,
+            "avg60" => record.avg60 = value.parse().ok()?,
+            "avg300" => record.avg300 = value.parse().ok()?,
+            "total" => record.total = Duration::from_micros(value.parse().ok()?),
+            _ => {}
+        }
+    }
+    Some(record)
+}
+
+fn parse_pressure(content: &str) -> crate::Result<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in content.lines() {
+        let Some((kind, fields)) = line.split_once(' ') else { continue };
+        let Some(record) = parse_record(fields) else {
+            return Err( Error::Parse(format!("pressure line: {:?}", line)) );
+        };
+        match kind {
+            "some" => some = Some(record),
+            "full" => full = Some(record),
+            _ => {}
+        }
+    }
+
+    let Some(some) = some else {
+        return Err( Error::Parse(format!("pressure without `some` line: {:?}", content)) );
+    };
+    Ok(Pressure { some, full })
+}
+
+fn unsupported(source: std::io::Error) -> Error {
+    Error::Unsupported {
+        what: "pressure stall information without linux 4.20+, CONFIG_PSI or psi enabled".to_string(),
+        source: Some(source.into()),
+    }
+}
+
+fn read_pressure(path: &Path) -> crate::Result<Pressure> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => parse_pressure(&content),
+        // the file is missing without CONFIG_PSI, and reading fails with EOPNOTSUPP if `psi=0`.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound
+            || e.raw_os_error() == Some(libc::EOPNOTSUPP) =>
+        {
+            Err(unsupported(e))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// whether the kernel provides pressure stall information.
+pub fn is_supported() -> bool {
+    read_pressure( Path::new("/proc/pressure/cpu") ).is_ok()
+}
+
+/// the stalled time between two samples.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PressureDelta {
+    /// the time some tasks are stalled.
+    pub some: Duration,
+
+    /// the time all non-idle tasks are stalled, `None` if unavailable.
+    pub full: Option<Duration>,
+
+    /// the real time elapsed between two samples.
+    pub elapsed: Duration,
+}
+
+impl PressureDelta {
+    fn ratio(&self, stalled: Duration) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        stalled.as_secs_f64() / elapsed
+    }
+
+    /// the fraction of time some tasks are stalled.
+    pub fn some_ratio(&self) -> f64 {
+        self.ratio(self.some)
+    }
+
+    /// the fraction of time all non-idle tasks are stalled.
+    pub fn full_ratio(&self) -> Option<f64> {
+        self.full.map(|full| { self.ratio(full) })
+    }
+}
+
+/// A struct to monitor the pressure of a resource, system-wide or of a cgroup.
+pub struct PressureStat {
+    path: PathBuf,
+    last_stat: Cell<(Pressure, Instant)>,
+}
+
+impl PressureStat {
+    fn new(path: PathBuf) -> crate::Result<Self> {
+        let pressure = read_pressure(&path)?;
+        let now = Instant::now();
+        Ok(PressureStat {
+            path,
+            last_stat: Cell::new( (pressure, now) ),
+        })
+    }
+
+    /// return a monitor of system-wide pressure, it fails if PSI is unsupported.
+    pub fn system(resource: Resource) -> crate::Result<Self> {
+        Self::new( PathBuf::from(format!("/proc/pressure/{}", resource.name())) )
+    }
+
+    /// return a monitor of the pressure of the cgroup v2 current process belongs to.
+    ///
+    /// `None` if current process is not in a cgroup v2 hierarchy,
+    /// it fails if PSI is unsupported.
+    pub fn cgroup(resource: Resource) -> crate::Result<Option<Self>> {
+        let Some(cgroup) = Cgroup::unified()? else {
+            return Ok(None);
+        };
+        let path = cgroup.path.join( format!("{}.pressure", resource.name()) );
+        Ok(Some( Self::new(path)? ))
+    }
+
+    /// return the current pressure, it does not affect the baseline of `sample`.
+    pub fn pressure(&self) -> crate::Result<Pressure> {
+        read_pressure(&self.path)
+    }
+
+    /// return the stalled time from last invoke,
+    /// or when this struct created if it is the first invoke.
+    pub fn sample(&self) -> crate::Result<PressureDelta> {
+        let pressure = read_pressure(&self.path)?;
+        let now = Instant::now();
+
+        let (old_pressure, old_now) =
+            self.last_stat.replace(
+                (pressure, now)
+            );
+
+        Ok(PressureDelta {
+            some: pressure.some.total.saturating_sub(old_pressure.some.total),
+            full:
+                pressure.full.zip(old_pressure.full)
+                .map(|(a, b)| { a.total.saturating_sub(b.total) }),
+            elapsed: now.saturating_duration_since(old_now),
+        })
+    }
+}
+
+/// The system load average, read from `/proc/loadavg`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct LoadAvg {
+    /// the average number of runnable or uninterruptible tasks in the last minute.
+    pub one: f64,
+
+    /// the average in the last 5 minutes.
+    pub five: f64,
+
+    /// the average in the last 15 minutes.
+    pub fifteen: f64,
+
+    /// the number of currently runnable tasks.
+    pub runnable: u32,
+
+    /// the number of tasks that currently exist.
+    pub total: u32,
+}
+
+/// get the system load average.
+pub fn load_avg() -> crate::Result<LoadAvg> {
+    use procfs::Current;
+
+    let load = procfs::LoadAverage::current()?;
+    Ok(LoadAvg {
+        one: load.one.into(),
+        five: load.five.into(),
+        fifteen: load.fifteen.into(),
+        runnable: load.cur,
+        total: load.max,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pressure() {
+        let content =
+            "some avg10=2.38 avg60=2.36 avg300=2.53 total=59717379\n\
+             full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n";
+        let pressure = parse_pressure(content).unwrap();
+        assert_eq!(pressure.some.avg10, 2.38);
+        assert_eq!(pressure.some.total, Duration::from_micros(59717379));
+        assert_eq!(pressure.full.unwrap().total, Duration::ZERO);
+
+        if is_supported() {
+            let stat = PressureStat::system(Resource::Memory).unwrap();
+            let delta = stat.sample().unwrap();
+            assert!(delta.some_ratio() <= 1.0);
+        } else {
+            assert!(PressureStat::system(Resource::Cpu).is_err());
+        }
+
+        let load = load_avg().unwrap();
+        assert!(load.total >= load.runnable);
+    }
+}
@@ -0,0 +1,310 @@
+//! Measure the cost of a block of code on current thread.
+//!
+//! A [`CpuSpan`] records the wall time, the cpu time of current thread and,
+//! when [`CountingAllocator`] is installed and enabled, the bytes allocated.
+//!
+//! ```
+//! let (sum, measurement) = perfmon::span::measure(|| {
+//!     (0..1_000_000).into_iter().sum::<u64>()
+//! }).unwrap();
+//! println!("sum {} in {:?}, cpu {:?}", sum, measurement.wall_time, measurement.cpu_times.total());
+//! ```
+//!
+//! Spans created by [`CpuSpan::named`] or [`measure_named`] are aggregated per name
+//! into histograms, which can be dumped by [`span_stats`] later.
+//!
+//! ```
+//! for _ in 0..10 {
+//!     let _span = perfmon::span::CpuSpan::named("sum").unwrap();
+//!     let _ = (0..1_000_000).into_iter().sum::<u64>();
+//! }
+//! for (name, stats) in perfmon::span::span_stats() {
+//!     println!("{}: {} times, p99 cpu time {:?}", name, stats.count, stats.cpu_histogram.percentile(0.99));
+//! }
+//! ```
+
+use crate::cpu::{CpuTimes, ThreadId};
+use crate::mem::CountingAllocator;
+
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+
+/// The cost of a span.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SpanMeasurement {
+    /// the real time elapsed.
+    pub wall_time: Duration,
+
+    /// the cpu time of current thread in user mode and system mode.
+    pub cpu_times: CpuTimes,
+
+    /// the bytes allocated by rust allocations, including the growth of reallocations,
+    /// `None` if [`CountingAllocator`] is not enabled.
+    ///
+    /// the counter is process-wide, so allocations of other threads are included.
+    pub allocated_bytes: Option<u64>,
+
+    /// the change of bytes in use, that is the bytes allocated minus the bytes freed,
+    /// `None` if [`CountingAllocator`] is not enabled.
+    ///
+    /// it can be negative if memory allocated before the span is freed, by any thread.
+    pub net_allocated_bytes: Option<isize>,
+}
+
+/// A guard measuring the code from its creation to [`CpuSpan::finish`] or drop.
+///
+/// it measures the thread creating it, so it should be finished on the same thread.
+///
+/// the allocated bytes are read from the process-wide counters of [`CountingAllocator`],
+/// so they include the allocations and deallocations of other threads during the span.
+pub struct CpuSpan {
+    name: Option<&'static str>,
+    tid: ThreadId,
+    start: Instant,
+    start_cpu_times: CpuTimes,
+    allocation: Option<(u64, isize)>,
+}
+
+/// the total allocated bytes and the bytes in use, `None` if the counter is disabled.
+fn allocation() -> Option<(u64, isize)> {
+    if CountingAllocator::is_enable() {
+        Some( (CountingAllocator::get_total_allocated(), CountingAllocator::get_allocated()) )
+    } else {
+        None
+    }
+}
+
+impl CpuSpan {
+    fn new(name: Option<&'static str>) -> crate::Result<Self> {
+        let tid = ThreadId::current();
+        let start_cpu_times = tid.cpu_times()?;
+        Ok(CpuSpan {
+            name,
+            tid,
+            start: Instant::now(),
+            start_cpu_times,
+            allocation: allocation(),
+        })
+    }
+
+    /// start an anonymous span, it is not aggregated.
+    pub fn start() -> crate::Result<Self> {
+        Self::new(None)
+    }
+
+    /// start a span aggregated into the stats of `name` when it is finished or dropped.
+    pub fn named(name: &'static str) -> crate::Result<Self> {
+        Self::new(Some(name))
+    }
+
+    fn measure(&self) -> crate::Result<SpanMeasurement> {
+        let cpu_times = self.tid.cpu_times()?.saturating_sub(&self.start_cpu_times);
+        let wall_time = self.start.elapsed();
+        let allocation = self.allocation.zip(allocation());
+
+        Ok(SpanMeasurement {
+            wall_time,
+            cpu_times,
+            allocated_bytes: allocation.map(|(start, end)| { end.0.saturating_sub(start.0) }),
+            net_allocated_bytes: allocation.map(|(start, end)| { end.1 - start.1 }),
+        })
+    }
+
+    /// stop this span and return its cost.
+    pub fn finish(mut self) -> crate::Result<SpanMeasurement> {
+        let name = self.name.take();
+        let measurement = self.measure()?;
+        if let Some(name) = name {
+            record(name, &measurement);
+        }
+        Ok(measurement)
+    }
+}
+
+impl Drop for CpuSpan {
+    fn drop(&mut self) {
+        let Some(name) = self.name.take() else { return };
+        if let Ok(measurement) = self.measure() {
+            record(name, &measurement);
+        }
+    }
+}
+
+/// run `f` and return its result with the cost.
+///
+/// `f` is not run if the span fails to start.
+pub fn measure<F, R>(f: F) -> crate::Result<(R, SpanMeasurement)>
+where
+    F: FnOnce() -> R,
+{
+    let span = CpuSpan::start()?;
+    let ret = f();
+    Ok( (ret, span.finish()?) )
+}
+
+/// like [`measure`], but the cost is also aggregated into the stats of `name`.
+pub fn measure_named<F, R>(name: &'static str, f: F) -> crate::Result<(R, SpanMeasurement)>
+where
+    F: FnOnce() -> R,
+{
+    let span = CpuSpan::named(name)?;
+    let ret = f();
+    Ok( (ret, span.finish()?) )
+}
+
+/// A histogram of durations, bucketed by power of two nanoseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurationHistogram {
+    /// `buckets[i]` counts the durations in `[2^i, 2^(i+1))` ns, zero is counted in `buckets[0]`.
+    buckets: [u64; 64],
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        DurationHistogram { buckets: [0; 64] }
+    }
+}
+
+impl DurationHistogram {
+    fn bucket_of(d: Duration) -> usize {
+        let nanos: u64 = d.as_nanos().try_into().unwrap_or(u64::MAX);
+        nanos.max(1).ilog2() as usize
+    }
+
+    fn upper_bound(bucket: usize) -> Duration {
+        Duration::from_nanos( 1u64.checked_shl(bucket as u32 + 1).unwrap_or(u64::MAX) )
+    }
+
+    pub fn record(&mut self, d: Duration) {
+        self.buckets[Self::bucket_of(d)] += 1;
+    }
+
+    /// the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// the non-empty buckets as (exclusive upper bound, count), in ascending order.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets.iter()
+            .enumerate()
+            .filter(|(_, &count)| { count > 0 })
+            .map(|(i, &count)| { (Self::upper_bound(i), count) })
+    }
+
+    /// the upper bound of the bucket where the `q` (0.0 ~ 1.0) quantile falls in.
+    pub fn percentile(&self, q: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bound, n) in self.buckets() {
+            seen += n;
+            if seen >= rank {
+                return bound;
+            }
+        }
+        Duration::MAX
+    }
+}
+
+/// The aggregated cost of spans with the same name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanStats {
+    /// the number of finished spans.
+    pub count: u64,
+
+    /// the sum of wall time.
+    pub wall_time: Duration,
+
+    /// the sum of cpu time.
+    pub cpu_times: CpuTimes,
+
+    /// the sum of allocated bytes, of spans measured with [`CountingAllocator`] enabled.
+    pub allocated_bytes: u64,
+
+    /// the sum of the change of bytes in use, of spans measured with [`CountingAllocator`] enabled.
+    pub net_allocated_bytes: isize,
+
+    pub wall_histogram: DurationHistogram,
+
+    /// the histogram of cpu time in user mode and system mode.
+    pub cpu_histogram: DurationHistogram,
+}
+
+static SPANS: Lazy<Mutex<HashMap<&'static str, SpanStats>>> =
+    Lazy::new(|| { Mutex::new(HashMap::new()) });
+
+fn record(name: &'static str, measurement: &SpanMeasurement) {
+    let mut spans =
+        SPANS.lock()
+        .unwrap_or_else(|e| { e.into_inner() });
+
+    let stats = spans.entry(name).or_default();
+    stats.count += 1;
+    stats.wall_time = stats.wall_time.saturating_add(measurement.wall_time);
+    stats.cpu_times = stats.cpu_times.saturating_add(&measurement.cpu_times);
+    stats.allocated_bytes = stats.allocated_bytes.saturating_add(measurement.allocated_bytes.unwrap_or(0));
+    stats.net_allocated_bytes += measurement.net_allocated_bytes.unwrap_or(0);
+    stats.wall_histogram.record(measurement.wall_time);
+    stats.cpu_histogram.record(measurement.cpu_times.total());
+}
+
+/// return the aggregated stats of every named span, sorted by name.
+pub fn span_stats() -> Vec<(&'static str, SpanStats)> {
+    let spans =
+        SPANS.lock()
+        .unwrap_or_else(|e| { e.into_inner() });
+
+    let mut stats: Vec<_> =
+        spans.iter()
+        .map(|(name, stats)| { (*name, stats.clone()) })
+        .collect();
+    stats.sort_by_key(|(name, _)| { *name });
+    stats
+}
+
+/// clear the aggregated stats of all named spans.
+pub fn reset_span_stats() {
+    SPANS.lock()
+        .unwrap_or_else(|e| { e.into_inner() })
+        .clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_span() {
+        let (sum, measurement) =
+            measure(|| {
+                (0..10_000_000u64).map(std::hint::black_box).sum::<u64>()
+            })
+            .unwrap();
+        assert_eq!(sum, 49_999_995_000_000);
+        assert!(measurement.wall_time > Duration::ZERO);
+        assert!(measurement.cpu_times.total() <= measurement.wall_time + Duration::from_millis(10));
+
+        for _ in 0..3 {
+            let _span = CpuSpan::named("perfmon-test-span").unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let (_, stats) =
+            span_stats().into_iter()
+            .find(|(name, _)| { *name == "perfmon-test-span" })
+            .unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.wall_histogram.count(), 3);
+        assert!(stats.wall_time >= Duration::from_millis(3));
+        assert!(stats.wall_histogram.percentile(0.5) >= Duration::from_millis(1));
+    }
+}
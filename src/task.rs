@@ -0,0 +1,244 @@
+//! Cpu accounting of async tasks.
+//!
+//! Async tasks share the worker threads of a runtime, so the cpu usage of a thread
+//! tells nothing about a single task. [`Instrumented`] wraps a future and measures
+//! the cpu time of current thread consumed in each `poll`.
+//!
+//! A poll taking a long time usually means blocking code in async context,
+//! which stalls every other task on the same worker thread.
+//!
+//! ```
+//! # use perfmon::task::{task_stats, Instrumented};
+//! let task = Instrumented::new("my-task", async {
+//!     (0..1_000_000).into_iter().sum::<u64>()
+//! });
+//! // spawn `task` on any runtime, then
+//! for (name, stats) in task_stats() {
+//!     println!("{}: {} polls, cpu {:?}, longest poll {:?}", name, stats.polls, stats.cpu_time, stats.longest_poll);
+//! }
+//! ```
+
+use crate::cpu::ThreadId;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+
+/// The cost of polling a task, or all tasks with the same name.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TaskStats {
+    /// the number of tasks created, it is always 1 for a single task.
+    pub tasks: u64,
+
+    /// the number of tasks completed.
+    pub completed: u64,
+
+    /// the number of `poll` invoked.
+    pub polls: u64,
+
+    /// the cpu time of polling thread consumed in `poll`.
+    pub cpu_time: Duration,
+
+    /// the real time spent in `poll`.
+    pub busy_time: Duration,
+
+    /// the longest real time spent in a single `poll`.
+    pub longest_poll: Duration,
+}
+
+impl TaskStats {
+    fn record(&mut self, cpu_time: Duration, busy_time: Duration, ready: bool) {
+        self.polls += 1;
+        self.cpu_time = self.cpu_time.saturating_add(cpu_time);
+        self.busy_time = self.busy_time.saturating_add(busy_time);
+        self.longest_poll = self.longest_poll.max(busy_time);
+        if ready {
+            self.completed += 1;
+        }
+    }
+
+    /// the average real time spent in a single `poll`.
+    pub fn avg_poll(&self) -> Duration {
+        match u32::try_from(self.polls) {
+            Ok(0) => Duration::ZERO,
+            Ok(n) => self.busy_time / n,
+            Err(_) => Duration::from_secs_f64(
+                self.busy_time.as_secs_f64() / (self.polls as f64)
+            ),
+        }
+    }
+}
+
+type SharedTaskStats = Arc<Mutex<TaskStats>>;
+
+static TASKS: Lazy<Mutex<HashMap<&'static str, SharedTaskStats>>> =
+    Lazy::new(|| { Mutex::new(HashMap::new()) });
+
+/// A future measuring the cost of polling the wrapped future.
+pub struct Instrumented<F> {
+    inner: F,
+    name: &'static str,
+    stats: TaskStats,
+
+    /// shared by all tasks with the same name.
+    total: SharedTaskStats,
+}
+
+impl<F: Future> Instrumented<F> {
+    /// wrap `future`, its cost is also aggregated into the stats of `name`.
+    pub fn new(name: &'static str, future: F) -> Self {
+        let total =
+            TASKS.lock()
+            .unwrap_or_else(|e| { e.into_inner() })
+            .entry(name)
+            .or_default()
+            .clone();
+
+        total.lock()
+            .unwrap_or_else(|e| { e.into_inner() })
+            .tasks += 1;
+
+        Instrumented {
+            inner: future,
+            name,
+            stats: TaskStats {
+                tasks: 1,
+                ..TaskStats::default()
+            },
+            total,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// return the cost of this task so far.
+    pub fn stats(&self) -> TaskStats {
+        self.stats
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `inner` is never moved out, and the other fields are not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+
+        let tid = ThreadId::current();
+        let start_cpu = tid.cpu_time().ok();
+        let start = Instant::now();
+
+        let ret = inner.poll(cx);
+
+        let busy_time = start.elapsed();
+        let cpu_time =
+            start_cpu.zip(tid.cpu_time().ok())
+            .map(|(start, end)| { end.saturating_sub(start) })
+            .unwrap_or_default();
+        let ready = ret.is_ready();
+
+        this.stats.record(cpu_time, busy_time, ready);
+        this.total.lock()
+            .unwrap_or_else(|e| { e.into_inner() })
+            .record(cpu_time, busy_time, ready);
+
+        ret
+    }
+}
+
+/// return the aggregated stats of every task name, sorted by name.
+pub fn task_stats() -> Vec<(&'static str, TaskStats)> {
+    let tasks =
+        TASKS.lock()
+        .unwrap_or_else(|e| { e.into_inner() });
+
+    let mut stats: Vec<_> =
+        tasks.iter()
+        .map(|(name, stats)| {
+            let stats = *stats.lock().unwrap_or_else(|e| { e.into_inner() });
+            (*name, stats)
+        })
+        .collect();
+    stats.sort_by_key(|(name, _)| { *name });
+    stats
+}
+
+/// clear the aggregated stats of all task names.
+///
+/// tasks alive keep aggregating into the stats that have been cleared,
+/// only tasks created later are visible in [`task_stats`].
+pub fn reset_task_stats() {
+    TASKS.lock()
+        .unwrap_or_else(|e| { e.into_inner() })
+        .clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::task::{Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// returns `Pending` for `polls - 1` times, burning cpu in each poll.
+    struct Busy {
+        polls: u32,
+    }
+
+    impl Future for Busy {
+        type Output = u64;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+            let sum = (0..10_000_000u64).map(std::hint::black_box).sum::<u64>();
+            self.polls -= 1;
+            if self.polls == 0 {
+                return Poll::Ready(sum);
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_instrumented() {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut task = Box::pin(Instrumented::new("perfmon-test-task", Busy { polls: 3 }));
+        let ret =
+            loop {
+                if let Poll::Ready(ret) = task.as_mut().poll(&mut cx) {
+                    break ret;
+                }
+            };
+        assert_eq!(ret, 49_999_995_000_000);
+
+        let stats = task.stats();
+        assert_eq!(stats.polls, 3);
+        assert_eq!(stats.completed, 1);
+        assert!(stats.cpu_time > Duration::ZERO);
+        assert!(stats.longest_poll <= stats.busy_time);
+        assert!(stats.avg_poll() <= stats.longest_poll);
+
+        let (_, total) =
+            task_stats().into_iter()
+            .find(|(name, _)| { *name == "perfmon-test-task" })
+            .unwrap();
+        assert_eq!(total, stats);
+    }
+}
@@ -0,0 +1,225 @@
+//! An opt-in registry mapping `std::thread::ThreadId` to [`cpu::ThreadId`](crate::cpu::ThreadId).
+//!
+//! [`ThreadStat`] needs the OS-level thread id, which is only available from inside
+//! the thread. Threads spawned by [`spawn`] (or calling [`register_current_thread`])
+//! record their OS-level thread id, spawn location and parent thread, so that
+//! a [`ThreadStat`] can be built for them from any other thread.
+//!
+//! ```
+//! let (tx, rx) = std::sync::mpsc::channel::<()>();
+//! let handle = perfmon::thread::spawn(move || {
+//!     let _ = (0..1_000_000).into_iter().sum::<u64>();
+//!     let _ = rx.recv();
+//! });
+//!
+//! // the thread is registered once `spawn` returns.
+//! let stat = perfmon::thread::stat_of(handle.thread().id()).unwrap();
+//! println!("cpu usage: {:?}", stat.cpu_usage());
+//! drop(tx);
+//! handle.join().unwrap();
+//! ```
+//!
+//! Registered threads are removed from the registry when they exit.
+
+use crate::cpu::{ThreadId, ThreadStat};
+use crate::Error;
+
+use core::panic::Location;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread::{Builder, JoinHandle};
+
+use once_cell::sync::Lazy;
+
+/// A thread recorded in the registry.
+#[derive(Debug, Clone)]
+pub struct RegisteredThread {
+    /// the thread id assigned by Rust standard library.
+    pub std_id: std::thread::ThreadId,
+
+    /// the thread id assigned by OS.
+    pub tid: ThreadId,
+
+    /// the name given by `std::thread::Builder`, if any.
+    pub name: Option<String>,
+
+    /// where the thread is spawned, or where [`register_current_thread`] is called.
+    pub location: &'static Location<'static>,
+
+    /// the thread that spawned this thread, if it is spawned by [`spawn`] or [`spawn_with`].
+    pub parent: Option<std::thread::ThreadId>,
+}
+
+impl RegisteredThread {
+    /// return a monitor of this thread.
+    pub fn stat(&self) -> crate::Result<ThreadStat> {
+        self.tid.try_into()
+    }
+}
+
+static REGISTRY: Lazy<Mutex<HashMap<std::thread::ThreadId, RegisteredThread>>> =
+    Lazy::new(|| { Mutex::new(HashMap::new()) });
+
+/// removes the current thread from registry when the thread exits.
+struct Registration(std::thread::ThreadId);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = REGISTRY.lock() {
+            registry.remove(&self.0);
+        }
+    }
+}
+
+thread_local! {
+    static REGISTRATION: core::cell::OnceCell<Registration> =
+        const { core::cell::OnceCell::new() };
+}
+
+fn register(
+    location: &'static Location<'static>,
+    parent: Option<std::thread::ThreadId>,
+) -> RegisteredThread {
+    let thread = std::thread::current();
+    let std_id = thread.id();
+
+    let mut registry =
+        REGISTRY.lock()
+        .unwrap_or_else(|e| { e.into_inner() });
+
+    let info =
+        registry.entry(std_id)
+        .or_insert_with(|| {
+            RegisteredThread {
+                std_id,
+                tid: ThreadId::current(),
+                name: thread.name().map(|x| { x.to_string() }),
+                location,
+                parent,
+            }
+        })
+        .clone();
+    drop(registry);
+
+    REGISTRATION.with(|r| {
+        r.get_or_init(|| { Registration(std_id) });
+    });
+    info
+}
+
+/// record current thread in registry, and return the recorded info.
+///
+/// calling it more than once is harmless, the first record is kept.
+#[track_caller]
+pub fn register_current_thread() -> RegisteredThread {
+    register(Location::caller(), None)
+}
+
+/// spawn a new thread like `std::thread::spawn`, and record it in registry.
+#[track_caller]
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_with(Builder::new(), f)
+        .expect("failed to spawn thread")
+}
+
+/// spawn a new thread by provided `std::thread::Builder`, and record it in registry.
+///
+/// it returns after the new thread is recorded, so [`stat_of`] works on the returned handle.
+#[track_caller]
+pub fn spawn_with<F, T>(builder: Builder, f: F)
+    -> crate::Result<JoinHandle<T>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let location = Location::caller();
+    let parent = std::thread::current().id();
+
+    // the new thread must not block on the channel, or its park token may be consumed there
+    // and a later `park` in `f` would miss an `unpark`.
+    let (registered_tx, registered_rx) = std::sync::mpsc::channel();
+    let handle =
+        builder.spawn(move || {
+            register(location, Some(parent));
+            let _ = registered_tx.send(());
+            f()
+        })?;
+
+    // the sender is dropped if the thread is gone, so it never blocks forever.
+    let _ = registered_rx.recv();
+    Ok( handle )
+}
+
+/// return the recorded info of a registered thread.
+pub fn lookup(id: std::thread::ThreadId) -> Option<RegisteredThread> {
+    REGISTRY.lock()
+        .unwrap_or_else(|e| { e.into_inner() })
+        .get(&id)
+        .cloned()
+}
+
+/// return the recorded info of all alive registered threads.
+pub fn registered_threads() -> Vec<RegisteredThread> {
+    REGISTRY.lock()
+        .unwrap_or_else(|e| { e.into_inner() })
+        .values()
+        .cloned()
+        .collect()
+}
+
+/// return a monitor of a registered thread.
+pub fn stat_of(id: std::thread::ThreadId) -> crate::Result<ThreadStat> {
+    match lookup(id) {
+        Some(info) => info.stat(),
+        None => Err( Error::InvalidArgument(format!("thread {:?} is not registered", id)) ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle =
+            spawn_with(
+                Builder::new().name("registered".to_string()),
+                move || {
+                    tx.send(ThreadId::current()).unwrap();
+                    std::thread::park();
+                },
+            )
+            .unwrap();
+        let tid = rx.recv().unwrap();
+        let std_id = handle.thread().id();
+
+        let info = lookup(std_id).unwrap();
+        assert_eq!(info.tid, tid);
+        assert_eq!(info.name.as_deref(), Some("registered"));
+        assert_eq!(info.location.file(), file!());
+        assert_eq!(info.parent, Some(std::thread::current().id()));
+        assert!(stat_of(std_id).unwrap().cpu_usage().is_ok());
+
+        handle.thread().unpark();
+        handle.join().unwrap();
+        assert!(lookup(std_id).is_none());
+        assert!(stat_of(std_id).is_err());
+    }
+
+    #[test]
+    fn test_stat_of_after_spawn() {
+        for _ in 0..100 {
+            let handle = spawn(|| { std::thread::park(); });
+            assert!(stat_of(handle.thread().id()).is_ok());
+
+            handle.thread().unpark();
+            handle.join().unwrap();
+        }
+    }
+}
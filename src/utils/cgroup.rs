@@ -0,0 +1,125 @@
+//! Locate the cgroup directories of current process, or of any process.
+//!
+//! <https://man7.org/linux/man-pages/man7/cgroups.7.html>
+
+use std::path::{Path, PathBuf};
+
+use crate::utils::process::get_process;
+
+use procfs::process::{MountInfo, Process};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// A cgroup directory that current process belongs to.
+#[derive(Debug, Clone)]
+pub struct Cgroup {
+    pub version: CgroupVersion,
+
+    /// where the hierarchy is mounted, the cgroup can not be walked up beyond it.
+    pub mount_point: PathBuf,
+
+    /// the directory of cgroup.
+    pub path: PathBuf,
+}
+
+fn cgroup_path(mount: &MountInfo, pathname: &str) -> PathBuf {
+    // the mount root is not "/" when the hierarchy is bind-mounted into a container.
+    let relative =
+        match pathname.strip_prefix(mount.root.as_str()) {
+            Some(relative) => relative,
+            None if mount.root == "/" => pathname,
+            None => "",
+        };
+
+    mount.mount_point.join( relative.trim_start_matches('/') )
+}
+
+impl Cgroup {
+    /// return the cgroup of current process which `controller` (e.g. "cpu", "cpuset") is attached to.
+    ///
+    /// a cgroup v1 hierarchy is preferred, since a controller bound to v1
+    /// is not available in v2 (unified) hierarchy.
+    pub fn of_controller(controller: &str) -> crate::Result<Option<Self>> {
+        Self::of_process_controller(std::process::id(), controller)
+    }
+
+    /// return the cgroup of the process specified by `pid` which `controller` is attached to.
+    ///
+    /// the cgroup paths of that process are resolved against the mounts of current process,
+    /// as `/proc/{pid}/cgroup` is shown relative to the cgroup namespace of the reader.
+    pub fn of_process_controller(pid: u32, controller: &str) -> crate::Result<Option<Self>> {
+        let cgroups = get_process(pid)?.cgroups()?.0;
+        let mounts = Process::myself()?.mountinfo()?.0;
+
+        for cgroup in cgroups.iter() {
+            if ! cgroup.controllers.iter().any(|c| { c == controller }) {
+                continue;
+            }
+            let mount =
+                mounts.iter()
+                .find(|m| {
+                    m.fs_type == "cgroup"
+                    && m.super_options.contains_key(controller)
+                });
+            if let Some(mount) = mount {
+                return Ok(Some(Cgroup {
+                    version: CgroupVersion::V1,
+                    mount_point: mount.mount_point.clone(),
+                    path: cgroup_path(mount, &cgroup.pathname),
+                }));
+            }
+        }
+
+        let Some(unified) = Self::unified_of_process(pid)? else {
+            return Ok(None);
+        };
+        // controllers enabled in root cgroup are not listed, but root cgroup has no limits.
+        let enabled =
+            unified.read("cgroup.controllers")
+            .map(|c| { c.split_whitespace().any(|c| { c == controller }) })
+            .unwrap_or(false);
+        if enabled || unified.path == unified.mount_point {
+            return Ok(Some(unified));
+        }
+        Ok(None)
+    }
+
+    /// return the cgroup v2 (unified hierarchy) of current process.
+    pub fn unified() -> crate::Result<Option<Self>> {
+        Self::unified_of_process(std::process::id())
+    }
+
+    /// return the cgroup v2 (unified hierarchy) of the process specified by `pid`.
+    pub fn unified_of_process(pid: u32) -> crate::Result<Option<Self>> {
+        let cgroups = get_process(pid)?.cgroups()?.0;
+        let mounts = Process::myself()?.mountinfo()?.0;
+
+        let Some(cgroup) = cgroups.iter().find(|c| { c.hierarchy == 0 }) else {
+            return Ok(None);
+        };
+        let Some(mount) = mounts.iter().find(|m| { m.fs_type == "cgroup2" }) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Cgroup {
+            version: CgroupVersion::V2,
+            mount_point: mount.mount_point.clone(),
+            path: cgroup_path(mount, &cgroup.pathname),
+        }))
+    }
+
+    /// read a file of this cgroup.
+    pub fn read(&self, file: &str) -> std::io::Result<String> {
+        std::fs::read_to_string(self.path.join(file))
+    }
+
+    /// this cgroup and its ancestors, up to the mount point.
+    pub fn ancestors(&self) -> impl Iterator<Item = &Path> {
+        self.path.ancestors()
+            .take_while(|p| { p.starts_with(&self.mount_point) })
+    }
+}
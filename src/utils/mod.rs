@@ -1,3 +1,9 @@
 pub mod ptr_upgrade;
 #[cfg(windows)]
 pub mod windows_handle;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod cgroup;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod sysfs;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod process;
@@ -0,0 +1,20 @@
+//! Open processes from procfs with errors telling whether the process has exited.
+
+use crate::Error;
+
+use procfs::process::Process;
+use procfs::ProcError;
+
+/// whether `/proc` is mounted, a missing file in `/proc` means the process exited only if it is.
+pub fn procfs_mounted() -> bool {
+    std::path::Path::new("/proc/self").exists()
+}
+
+/// open the process specified by `pid`, fails with [`Error::ProcessExited`] if it does not exist.
+pub fn get_process(pid: u32) -> crate::Result<Process> {
+    match Process::new(pid.try_into()?) {
+        Ok(process) => Ok(process),
+        Err(ProcError::NotFound(_)) if procfs_mounted() => Err( Error::ProcessExited { pid } ),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,30 @@
+//! Helpers to read the plain text files of sysfs and procfs.
+
+use std::path::Path;
+use std::str::FromStr;
+
+/// read a file containing a single value, like `/sys/devices/system/cpu/cpu0/topology/core_id`.
+pub fn read_value<T: FromStr>(path: impl AsRef<Path>) -> Option<T> {
+    std::fs::read_to_string(path).ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// parse a cpu list like `0-3,5,7-8`.
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|r| { ! r.is_empty() }) {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start.parse().ok()?;
+                let end: usize = end.parse().ok()?;
+                cpus.extend(start..=end);
+            }
+            None => {
+                cpus.push(range.parse().ok()?);
+            }
+        }
+    }
+    Some(cpus)
+}